@@ -0,0 +1,317 @@
+use crate::prober::{probe_url, ProbeResult};
+use crate::signatures::{clip, Detection, SignatureSet};
+use chrono::Local;
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+
+// Bundled probe definitions, compiled in like the passive signatures
+const BUNDLED_PROBES: &str = include_str!("../data/active_probes.json");
+
+// Signature paths rarely need more than this to be recognised
+const MAX_PROBE_BODY: usize = 256 * 1024;
+
+// One probe as written in a probe file
+#[derive(Debug, Deserialize)]
+struct ProbeDef {
+    path: String,
+    tech: String,
+    #[serde(default)]
+    status: Vec<u16>,  // Accepted statuses (default: any 2xx)
+    body: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    version: Option<String>,  // Regex whose first group is the version
+    #[serde(default)]
+    sha256: HashMap<String, String>,  // Body hash -> version, for static files
+    confidence: Option<u8>,
+}
+
+// A probe with its patterns compiled
+#[derive(Debug)]
+pub struct ActiveProbe {
+    path: String,
+    tech: String,
+    status: Vec<u16>,
+    body: Option<Regex>,
+    headers: Vec<(String, Regex)>,
+    version: Option<Regex>,
+    sha256: HashMap<String, String>,
+    confidence: u8,
+}
+
+fn compile_regex(tech: &str, raw: &str) -> Option<Regex> {
+    match Regex::new(raw) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            eprintln!("Skipping active probe pattern '{}' for {}: {}", raw, tech, e);
+            None
+        }
+    }
+}
+
+impl ActiveProbe {
+    fn compile(def: ProbeDef) -> Option<ActiveProbe> {
+        let body = match &def.body {
+            Some(raw) => Some(compile_regex(&def.tech, raw)?),
+            None => None,
+        };
+        let mut headers = vec![];
+        for (name, raw) in &def.headers {
+            headers.push((name.to_lowercase(), compile_regex(&def.tech, raw)?));
+        }
+        let version = def.version.as_deref().and_then(|raw| compile_regex(&def.tech, raw));
+        Some(ActiveProbe {
+            path: def.path,
+            tech: def.tech,
+            status: def.status,
+            body,
+            headers,
+            version,
+            sha256: def.sha256.into_iter().map(|(h, v)| (h.to_lowercase(), v)).collect(),
+            confidence: def.confidence.unwrap_or(100).min(100),
+        })
+    }
+
+    // Returns (evidence, version) when the response satisfies every condition
+    fn check(&self, result: &ProbeResult, body: &str) -> Option<(String, Option<String>)> {
+        let status_ok = if self.status.is_empty() {
+            (200..300).contains(&result.status)
+        } else {
+            self.status.contains(&result.status)
+        };
+        if !status_ok {
+            return None;
+        }
+
+        let mut evidence = vec![format!("status {}", result.status)];
+        if let Some(re) = &self.body {
+            evidence.push(format!("body: {}", clip(re.find(body)?.as_str())));
+        }
+        for (name, re) in &self.headers {
+            let value = result.headers.get_all(name).find(|v| re.is_match(v))?;
+            evidence.push(format!("header {}: {}", name, clip(value)));
+        }
+
+        // Known static file hashes pin an exact version; a hash-only probe needs a hit
+        let hashed = result.body_sha256.as_ref().and_then(|h| self.sha256.get(h));
+        if let Some(version) = hashed {
+            evidence.push("sha256 match".to_string());
+            return Some((format!("active {} ({})", self.path, evidence.join(", ")), Some(version.clone())));
+        }
+        if !self.sha256.is_empty() && self.body.is_none() && self.headers.is_empty() {
+            return None;
+        }
+
+        let version = self
+            .version
+            .as_ref()
+            .and_then(|re| re.captures(body))
+            .and_then(|c| c.get(1))
+            .map(|m| m.as_str().to_string());
+        Some((format!("active {} ({})", self.path, evidence.join(", ")), version))
+    }
+}
+
+// Parses a JSON list of probe definitions
+fn parse_probes(source: &str, content: &str) -> Result<Vec<ActiveProbe>, String> {
+    let defs: Vec<ProbeDef> =
+        serde_json::from_str(content).map_err(|e| format!("Invalid active probe file '{}': {}", source, e))?;
+    Ok(defs.into_iter().filter_map(ActiveProbe::compile).collect())
+}
+
+// Bundled probes plus an optional custom probe file
+pub fn load_probes(custom_file: Option<&str>) -> Vec<ActiveProbe> {
+    let mut probes = parse_probes("bundled", BUNDLED_PROBES).expect("Invalid bundled active probes");
+    if let Some(path) = custom_file {
+        match fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e)).and_then(|c| parse_probes(path, &c)) {
+            Ok(custom) => {
+                println!("Loaded {} custom active probe(s) from '{}'", custom.len(), path);
+                probes.extend(custom);
+            }
+            Err(e) => eprintln!("{}. Using bundled active probes only.", e),
+        }
+    }
+    probes
+}
+
+// A path that should not exist, used to recognise catch-all responses
+fn random_path() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(Local::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+    format!("/grimnir-{:016x}", hasher.finish())
+}
+
+// Lazily-filled active detections for one origin
+type ActiveSlot = Arc<OnceCell<Vec<Detection>>>;
+
+// Runs the active probes once per origin, within a per-host request budget
+#[derive(Clone)]
+pub struct ActiveFingerprinter {
+    probes: Arc<Vec<ActiveProbe>>,
+    signatures: Arc<SignatureSet>,
+    budget: usize,
+    headers: Vec<(String, String)>,
+    permits: Arc<Semaphore>,  // The scan's rate limit, one permit per probe request
+    hosts: Arc<Mutex<HashMap<String, ActiveSlot>>>,
+}
+
+impl ActiveFingerprinter {
+    pub fn new(
+        probes: Vec<ActiveProbe>,
+        signatures: Arc<SignatureSet>,
+        budget: usize,
+        headers: Vec<(String, String)>,
+        permits: Arc<Semaphore>,
+    ) -> ActiveFingerprinter {
+        ActiveFingerprinter {
+            probes: Arc::new(probes),
+            signatures,
+            budget,
+            headers,
+            permits,
+            hosts: Arc::default(),
+        }
+    }
+
+    // Raw detections for the page's origin (one per matching probe, merged
+    // into each page's result by the caller), probing it on first use
+    pub async fn get(&self, client: &Client, page_url: &str) -> Vec<Detection> {
+        let origin = match Url::parse(page_url) {
+            Ok(u) => u.origin().ascii_serialization(),
+            Err(_) => return vec![],
+        };
+        let cell = self.hosts.lock().unwrap().entry(origin.clone()).or_default().clone();
+        cell.get_or_init(|| self.probe_host(client, origin)).await.clone()
+    }
+
+    async fn fetch(&self, client: &Client, url: String) -> Option<ProbeResult> {
+        let _permit = self.permits.acquire().await.ok()?;
+        probe_url(url, client, "GET", None, self.headers.clone(), MAX_PROBE_BODY).await.ok()
+    }
+
+    async fn probe_host(&self, client: &Client, origin: String) -> Vec<Detection> {
+        if self.budget == 0 {
+            return vec![];
+        }
+        // Catch-all servers answer every path with the same page; its hash
+        // lets such responses be told apart from real signature files. This
+        // request is not counted: the budget is for probe paths.
+        let baseline = self.fetch(client, format!("{}{}", origin, random_path())).await;
+        let baseline_hash = baseline.and_then(|b| b.body_sha256);
+        let mut requests = 0;
+
+        // Probes sharing a path share one request, in file order
+        let mut paths: Vec<&str> = vec![];
+        for probe in self.probes.iter() {
+            if !paths.contains(&probe.path.as_str()) {
+                paths.push(&probe.path);
+            }
+        }
+
+        let mut detections = vec![];
+        for path in paths {
+            if requests >= self.budget {
+                eprintln!("Active fingerprinting budget ({}) reached for {}", self.budget, origin);
+                break;
+            }
+            requests += 1;
+            let result = match self.fetch(client, format!("{}{}", origin, path)).await {
+                Some(r) => r,
+                None => continue,
+            };
+            if result.body_sha256.is_some() && result.body_sha256 == baseline_hash {
+                continue;
+            }
+            let body = result.body_text();
+            for probe in self.probes.iter().filter(|p| p.path == path) {
+                if let Some((evidence, version)) = probe.check(&result, &body) {
+                    detections.push(self.signatures.detection(&probe.tech, version, probe.confidence, evidence));
+                }
+            }
+        }
+        detections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+    use sha2::{Digest, Sha256};
+    use std::time::Duration;
+
+    fn probe(json: &str) -> ActiveProbe {
+        parse_probes("test", &format!("[{}]", json)).unwrap().remove(0)
+    }
+
+    fn check(probe: &ActiveProbe, result: &ProbeResult) -> Option<(String, Option<String>)> {
+        probe.check(result, &result.body_text())
+    }
+
+    #[test]
+    fn every_condition_must_match() {
+        let probe = probe(
+            r#"{"path": "/wp-login.php", "tech": "WordPress", "body": "wp-submit",
+                "headers": {"Set-Cookie": "wordpress_test_cookie"}, "version": "ver=([\\d.]+)"}"#,
+        );
+        let page = r#"<input id="wp-submit"><link href="a.css?ver=6.4.2">"#;
+        let hit = test_response("http://t/probe", 200, &[("set-cookie", "wordpress_test_cookie=1")], page);
+        let (evidence, version) = check(&probe, &hit).unwrap();
+        assert_eq!(evidence, "active /wp-login.php (status 200, body: wp-submit, header set-cookie: wordpress_test_cookie=1)");
+        assert_eq!(version.as_deref(), Some("6.4.2"));
+        assert!(check(&probe, &test_response("http://t/probe", 200, &[], r#"<input id="wp-submit">"#)).is_none());
+        assert!(check(&probe, &test_response("http://t/probe", 404, &[("set-cookie", "wordpress_test_cookie=1")], "wp-submit")).is_none());
+    }
+
+    #[test]
+    fn statuses_default_to_2xx() {
+        let any = probe(r#"{"path": "/server-status", "tech": "Apache"}"#);
+        assert!(check(&any, &test_response("http://t/probe", 204, &[], "")).is_some());
+        assert!(check(&any, &test_response("http://t/probe", 403, &[], "")).is_none());
+        let forbidden = probe(r#"{"path": "/server-status", "tech": "Apache", "status": [403]}"#);
+        assert!(check(&forbidden, &test_response("http://t/probe", 403, &[], "")).is_some());
+    }
+
+    #[test]
+    fn static_file_hashes_pin_versions() {
+        let file = "/*! jQuery v3.7.1 */";
+        let hash = format!("{:X}", Sha256::digest(file.as_bytes()));
+        let hashed = probe(&format!(r#"{{"path": "/jquery.js", "tech": "jQuery", "sha256": {{"{}": "3.7.1"}}}}"#, hash));
+        let (evidence, version) = check(&hashed, &test_response("http://t/probe", 200, &[], file)).unwrap();
+        assert_eq!(evidence, "active /jquery.js (status 200, sha256 match)");
+        assert_eq!(version.as_deref(), Some("3.7.1"));
+        // A hash-only probe says nothing about files it does not know
+        assert!(check(&hashed, &test_response("http://t/probe", 200, &[], "/*! jQuery v3.7.0 */")).is_none());
+    }
+
+    #[test]
+    fn invalid_patterns_skip_the_probe() {
+        let probes = parse_probes("test", r#"[{"path": "/a", "tech": "A", "body": "("}, {"path": "/b", "tech": "B", "confidence": 150}]"#).unwrap();
+        assert_eq!(probes.len(), 1);
+        assert_eq!((probes[0].tech.as_str(), probes[0].confidence), ("B", 100));
+        assert!(parse_probes("test", "{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn probe_requests_wait_for_a_rate_permit() {
+        let client = Client::builder().no_proxy().build().unwrap();
+        let permits = Arc::new(Semaphore::new(0));
+        let active = || {
+            let probes = vec![probe(r#"{"path": "/a", "tech": "A", "body": "a"}"#)];
+            ActiveFingerprinter::new(probes, Arc::default(), 5, vec![], permits.clone())
+        };
+        let blocked = active();
+        let waiting = tokio::time::timeout(Duration::from_millis(100), blocked.get(&client, "http://127.0.0.1:9/"));
+        assert!(waiting.await.is_err());
+        permits.add_permits(1);
+        assert!(active().get(&client, "http://127.0.0.1:9/").await.is_empty());
+        assert_eq!(permits.available_permits(), 1);
+    }
+}
@@ -1,72 +1,367 @@
+use crate::anomaly::{Anomaly, AnomalyDetector};
+use crate::llm_triage::{LlmAnalyzer, LlmConfig, LlmVerdict};
+use crate::page_classifier::{Classification, PageClassifier};
 use crate::prober::ProbeResult;
-use rust_bert::pipelines::sentiment::{SentimentClassifier, Sentiment};
-use std::sync::OnceLock;
-
-// Lazy-loaded model (loads once, with fallback)
-fn sentiment_model() -> Option<&'static SentimentClassifier> {
-    static MODEL: OnceLock<Option<SentimentClassifier>> = OnceLock::new();
-    MODEL.get_or_init(|| {
-        match SentimentClassifier::new() {
-            Ok(model) => Some(model),
-            Err(e) => {
-                eprintln!("Failed to load sentiment model: {}. AI sentiment disabled.", e);
-                None
-            }
+use crate::score_rules::ScoreRules;
+use crate::secrets::{SecretFinding, SecretsAnalyzer};
+#[cfg(feature = "ai-bert")]
+use crate::sentiment::SentimentAnalyzer;
+use serde::Serialize;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+// Analyzers used when --analyzers is not given: the scoring plain --ai has
+// always had. The classifier (calibration requests), secrets, anomaly and llm
+// analyzers change requests or scores, so they are opt-in.
+#[cfg(feature = "ai-bert")]
+pub const DEFAULT_ANALYZERS: &str = "heuristics,sentiment";
+#[cfg(not(feature = "ai-bert"))]
+pub const DEFAULT_ANALYZERS: &str = "heuristics";
+
+// Analyzers that exist only when grimnir is built with a cargo feature
+const FEATURE_ANALYZERS: [(&str, &str); 1] = [("sentiment", "ai-bert")];
+
+// One contribution to a result's score, attributed to the analyzer that made it
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreComponent {
+    pub analyzer: String,
+    pub name: String,
+    pub value: f32,  // Positive raises interest, negative lowers it
+}
+
+// What the analyzers found for one result
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Analysis {
+    pub score: f32,  // Combined, 0.0-1.0
+    pub insights: Vec<String>,
+    pub components: Vec<ScoreComponent>,
+    pub label: Option<Classification>,  // Page type, set by the classifier analyzer
+    pub secrets: Vec<SecretFinding>,     // Leaked credentials and debug output, set by the secrets analyzer
+    pub triage: Option<LlmVerdict>,      // Severity/category/explanation, set by the llm analyzer
+    pub anomaly: Option<Anomaly>,        // Outlier score for the host, set by the anomaly analyzer
+}
+
+impl Analysis {
+    // Records a score component together with the insight that explains it
+    pub fn add(&mut self, analyzer: &str, name: &str, value: f32, insight: String) {
+        self.components.push(ScoreComponent { analyzer: analyzer.to_string(), name: name.to_string(), value });
+        self.insights.push(insight);
+    }
+}
+
+// Completion of one analyzer's work on a result
+pub type AnalyzerFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+// A source of score components and insights (heuristics, NLP models, team rules).
+// Implementations must be cheap to share: one instance serves every scan task.
+// Rule-based analyzers finish synchronously and return `done()`; model-backed
+// ones await an inference worker so the async runtime is never blocked.
+pub trait Analyzer: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn analyze<'a>(&'a self, result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a>;
+
+    // Whether the scan should send calibration requests before it starts
+    fn needs_calibration(&self) -> bool {
+        false
+    }
+
+    // Receives the responses to random, nonexistent words (word, response),
+    // i.e. what a miss looks like on this target
+    fn calibrate(&mut self, _baselines: &[(String, ProbeResult)]) {}
+
+    // Sees every kept result once the scan is over, for judgments that need
+    // the whole scan; scores are recombined afterwards
+    fn finish(&self, _results: &mut [(&ProbeResult, &mut Analysis)]) {}
+}
+
+// An already-finished analyzer future
+pub fn done<'a>() -> AnalyzerFuture<'a> {
+    Box::pin(std::future::ready(()))
+}
+
+// How per-analyzer subtotals are combined into the final score
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreFormula {
+    Sum,   // Weighted sum of every component (default)
+    Max,   // Highest weighted analyzer subtotal
+    Mean,  // Weighted mean of analyzer subtotals
+}
+
+impl ScoreFormula {
+    pub fn parse(s: &str) -> Option<ScoreFormula> {
+        match s.to_lowercase().as_str() {
+            "sum" => Some(ScoreFormula::Sum),
+            "max" => Some(ScoreFormula::Max),
+            "mean" | "avg" => Some(ScoreFormula::Mean),
+            _ => None,
         }
-    }).as_ref()  // Return Option<&'static>
+    }
 }
 
-// Analyzes a ProbeResult and returns a score (0.0-1.0) + insights
-pub fn analyze(result: &ProbeResult) -> (f32, String) {
-    let mut score = 0.0;
-    let mut insights = Vec::new();
+// Weighted rules over result fields (status, server, sensitive pages; see
+// data/score_rules.json), plus notes on weak session cookies
+pub struct HeuristicAnalyzer {
+    rules: ScoreRules,
+}
 
-    // Heuristic rules (simple "AI")
-    if result.status == 200 {
-        score += 0.5;
-        insights.push("Successful response".to_string());
+impl Analyzer for HeuristicAnalyzer {
+    fn name(&self) -> &'static str {
+        "heuristics"
     }
-    if let Some(server) = result.headers.get("server") {
-        if server.contains("Apache") || server.contains("Nginx") || server.contains("IIS") {
-            score += 0.2;
-            insights.push(format!("Interesting server: {}", server));
+
+    fn analyze<'a>(&'a self, result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a> {
+        self.rules.apply(self.name(), result, &[], analysis);
+        // Session-like cookies missing protective flags
+        for cookie in &result.cookies {
+            let cookie_name = cookie.name.to_lowercase();
+            if (cookie_name.contains("sess") || cookie_name.contains("token") || cookie_name.contains("auth"))
+                && (!cookie.http_only || !cookie.secure)
+            {
+                analysis.insights.push(format!(
+                    "Cookie '{}' lacks {}",
+                    cookie.name,
+                    if !cookie.http_only { "HttpOnly" } else { "Secure" }
+                ));
+            }
         }
+        done()
     }
-    if let Some(title) = &result.title {
-        if title.to_lowercase().contains("admin") || title.to_lowercase().contains("login") {
-            score += 0.3;
-            insights.push("Potential sensitive page".to_string());
+}
+
+// Where analyzers load their models and rule files from
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerConfig {
+    #[cfg_attr(not(feature = "ai-bert"), allow(dead_code))]
+    pub model_dir: Option<PathBuf>,         // Sentiment model directory (ai-bert builds)
+    pub classifier_model: Option<PathBuf>,  // Trained page classifier weights (JSON)
+    pub secret_rules: Option<PathBuf>,      // Extra secret rules, same format as data/secret_rules.json
+    pub llm: Option<LlmConfig>,             // Endpoint for the llm analyzer
+    pub score_rules: Option<PathBuf>,       // Extra/overriding score rules, same format as data/score_rules.json
+}
+
+// The enabled analyzers, each with a weight, and the combining formula
+pub struct AnalyzerRegistry {
+    analyzers: Vec<(Box<dyn Analyzer>, f32)>,
+    formula: ScoreFormula,
+}
+
+impl AnalyzerRegistry {
+    // Every analyzer compiled into this build
+    pub fn available() -> Vec<&'static str> {
+        let mut names = vec!["heuristics", "classifier", "secrets", "anomaly", "llm"];
+        if cfg!(feature = "ai-bert") {
+            names.push("sentiment");
         }
+        names
+    }
+
+    fn create(name: &str, config: &AnalyzerConfig) -> Result<Option<Box<dyn Analyzer>>, String> {
+        Ok(match name {
+            "heuristics" => Some(Box::new(HeuristicAnalyzer { rules: ScoreRules::load(config.score_rules.as_deref(), name)? })),
+            "classifier" => Some(Box::new(PageClassifier::new(config.classifier_model.as_deref())?)),
+            "secrets" => Some(Box::new(SecretsAnalyzer::new(config.secret_rules.as_deref())?)),
+            "anomaly" => Some(Box::new(AnomalyDetector::default())),
+            "llm" => match &config.llm {
+                Some(llm) => Some(Box::new(LlmAnalyzer::new(llm.clone(), config.secret_rules.as_deref())?)),
+                None => return Err("The llm analyzer needs --llm-endpoint".to_string()),
+            },
+            #[cfg(feature = "ai-bert")]
+            "sentiment" => Some(Box::new(SentimentAnalyzer::new(
+                config.model_dir.as_deref(),
+                ScoreRules::load(config.score_rules.as_deref(), name)?,
+            ))),
+            _ => None,
+        })
+    }
 
-        // Real AI: Sentiment analysis on title (skip if empty)
-        if !title.is_empty() {
-            if let Some(model) = sentiment_model() {
-                let sentiments: Vec<Sentiment> = model.predict(&[title.as_str()]);
-                if let Some(sentiment) = sentiments.first() {
-                    if sentiment.polarity == "negative" && sentiment.score > 0.7 {
-                        score -= 0.2;  // Downgrade likely error pages
-                        insights.push(format!("Negative sentiment in title (score: {:.2})", sentiment.score));
+    // Builds the registry from "name[:weight],..." (e.g. "heuristics,sentiment:0.5");
+    // model-backed analyzers load from the configured paths instead of downloading
+    pub fn from_spec(spec: &str, formula: &str, config: &AnalyzerConfig) -> Result<AnalyzerRegistry, String> {
+        let formula = ScoreFormula::parse(formula)
+            .ok_or_else(|| format!("Unknown score formula '{}'. Use sum, max or mean.", formula))?;
+        let mut registry = AnalyzerRegistry { analyzers: vec![], formula };
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, weight) = match entry.split_once(':') {
+                Some((n, w)) => (n.trim(), w.trim().parse::<f32>().map_err(|_| format!("Invalid weight in '{}'", entry))?),
+                None => (entry, 1.0),
+            };
+            let name = name.to_lowercase();
+            match AnalyzerRegistry::create(&name, config)? {
+                Some(analyzer) => registry.register(analyzer, weight),
+                None => match FEATURE_ANALYZERS.iter().find(|(n, _)| *n == name) {
+                    // Known, but its model backend was left out of this build
+                    Some((_, feature)) => eprintln!(
+                        "Analyzer '{}' is not compiled into this build. Rebuild with `cargo build --release --features {}` (needs libtorch) to enable it. Skipping.",
+                        name, feature
+                    ),
+                    None => {
+                        return Err(format!("Unknown analyzer '{}'. Available: {}", name, AnalyzerRegistry::available().join(", ")));
                     }
-                }
+                },
             }
         }
+        if registry.analyzers.is_empty() {
+            return Err("No analyzers enabled".to_string());
+        }
+        Ok(registry)
     }
 
-    // Enhancement: If body_snippet exists, run sentiment on it for more context
-    if let Some(body) = &result.body_snippet {
-        if !body.is_empty() {
-            if let Some(model) = sentiment_model() {
-                let sentiments: Vec<Sentiment> = model.predict(&[body.as_str()]);
-                if let Some(sentiment) = sentiments.first() {
-                    if sentiment.polarity == "negative" && sentiment.score > 0.8 {  // Slightly higher threshold for body
-                        score -= 0.1;  // Mild downgrade
-                        insights.push(format!("Negative sentiment in body snippet (score: {:.2})", sentiment.score));
-                    }
-                }
+    // Adds an analyzer; one with the same name is replaced
+    pub fn register(&mut self, analyzer: Box<dyn Analyzer>, weight: f32) {
+        self.analyzers.retain(|(a, _)| a.name() != analyzer.name());
+        self.analyzers.push((analyzer, weight));
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.analyzers.iter().map(|(a, _)| a.name()).collect()
+    }
+
+    pub fn needs_calibration(&self) -> bool {
+        self.analyzers.iter().any(|(a, _)| a.needs_calibration())
+    }
+
+    pub fn calibrate(&mut self, baselines: &[(String, ProbeResult)]) {
+        for (analyzer, _) in &mut self.analyzers {
+            analyzer.calibrate(baselines);
+        }
+    }
+
+    // Runs every analyzer and combines their components into a 0.0-1.0 score.
+    // The score is recombined after each analyzer, so a later one (llm) sees
+    // the weighted --score-formula score of the analyzers before it.
+    pub async fn analyze(&self, result: &ProbeResult) -> Analysis {
+        let mut analysis = Analysis::default();
+        for (analyzer, _) in &self.analyzers {
+            analyzer.analyze(result, &mut analysis).await;
+            self.combine(&mut analysis);
+        }
+        analysis
+    }
+
+    // Post-scan pass of every analyzer over the kept results, then rescoring
+    pub fn finish(&self, results: &mut [(&ProbeResult, &mut Analysis)]) {
+        for (analyzer, _) in &self.analyzers {
+            analyzer.finish(results);
+        }
+        for (_, analysis) in results.iter_mut() {
+            self.combine(analysis);
+        }
+    }
+
+    fn combine(&self, analysis: &mut Analysis) {
+        // (weighted subtotal, weight) per analyzer
+        let subtotals: Vec<(f32, f32)> = self
+            .analyzers
+            .iter()
+            .map(|(analyzer, weight)| {
+                let subtotal: f32 =
+                    analysis.components.iter().filter(|c| c.analyzer == analyzer.name()).map(|c| c.value).sum();
+                (subtotal * weight, *weight)
+            })
+            .collect();
+
+        let score = match self.formula {
+            ScoreFormula::Sum => subtotals.iter().map(|(s, _)| s).sum(),
+            ScoreFormula::Max => subtotals.iter().map(|(s, _)| *s).fold(f32::MIN, f32::max),
+            ScoreFormula::Mean => {
+                let weights: f32 = subtotals.iter().map(|(_, w)| w).sum();
+                if weights > 0.0 { subtotals.iter().map(|(s, _)| s).sum::<f32>() / weights } else { 0.0 }
+            }
+        };
+        // Normalize (an empty f32 sum is -0.0, which would print as "-0.00")
+        analysis.score = if score > 0.0 { score.min(1.0) } else { 0.0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::Headers;
+
+    // Adds fixed components under its own name
+    struct Fixed(&'static str, Vec<f32>);
+
+    impl Analyzer for Fixed {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn analyze<'a>(&'a self, _result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a> {
+            for value in &self.1 {
+                analysis.add(self.0, "fixed", *value, format!("{} {}", self.0, value));
             }
+            done()
+        }
+    }
+
+    fn registry(formula: ScoreFormula, analyzers: Vec<(Fixed, f32)>) -> AnalyzerRegistry {
+        let mut registry = AnalyzerRegistry { analyzers: vec![], formula };
+        for (analyzer, weight) in analyzers {
+            registry.register(Box::new(analyzer), weight);
         }
+        registry
+    }
+
+    fn result() -> ProbeResult {
+        ProbeResult::from_response("http://t/".to_string(), 200, Headers::default(), vec![], false, 0)
+    }
+
+    async fn score(formula: ScoreFormula) -> f32 {
+        let registry = registry(formula, vec![(Fixed("a", vec![0.2, 0.1]), 1.0), (Fixed("b", vec![0.4]), 0.5)]);
+        registry.analyze(&result()).await.score
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[tokio::test]
+    async fn formulas_combine_weighted_subtotals() {
+        assert!(close(score(ScoreFormula::Sum).await, 0.5));
+        assert!(close(score(ScoreFormula::Max).await, 0.3));
+        assert!(close(score(ScoreFormula::Mean).await, 0.5 / 1.5));
+    }
+
+    #[tokio::test]
+    async fn scores_are_clamped() {
+        let high = registry(ScoreFormula::Sum, vec![(Fixed("a", vec![0.9, 0.8]), 1.0)]);
+        assert_eq!(high.analyze(&result()).await.score, 1.0);
+        let low = registry(ScoreFormula::Sum, vec![(Fixed("a", vec![-0.4]), 1.0), (Fixed("b", vec![]), 1.0)]);
+        let analysis = low.analyze(&result()).await;
+        assert_eq!(analysis.score, 0.0);
+        assert_eq!(analysis.insights, ["a -0.4"]);
     }
 
-    score = score.clamp(0.0, 1.0);  // Normalize
-    (score, insights.join("; "))
+    #[test]
+    fn registering_a_name_twice_replaces_it() {
+        let registry = registry(ScoreFormula::Sum, vec![(Fixed("a", vec![]), 1.0), (Fixed("b", vec![]), 1.0), (Fixed("a", vec![]), 2.0)]);
+        assert_eq!(registry.names(), ["b", "a"]);
+        assert_eq!(registry.analyzers[1].1, 2.0);
+    }
+
+    #[test]
+    fn specs_and_formulas_are_validated() {
+        assert_eq!(ScoreFormula::parse("AVG"), Some(ScoreFormula::Mean));
+        assert_eq!(ScoreFormula::parse("median"), None);
+        let config = AnalyzerConfig::default();
+        let registry = AnalyzerRegistry::from_spec("heuristics:0.5, anomaly", "max", &config).unwrap();
+        assert_eq!(registry.names(), ["heuristics", "anomaly"]);
+        assert_eq!(registry.formula, ScoreFormula::Max);
+        assert!(AnalyzerRegistry::from_spec("heuristics", "median", &config).is_err());
+        assert!(AnalyzerRegistry::from_spec("heuristics:high", "sum", &config).is_err());
+        assert!(AnalyzerRegistry::from_spec("bogus", "sum", &config).err().unwrap().starts_with("Unknown analyzer 'bogus'"));
+        assert!(AnalyzerRegistry::from_spec(" , ", "sum", &config).is_err());
+        assert!(AnalyzerRegistry::from_spec("llm", "sum", &config).is_err());
+    }
+
+    #[cfg(not(feature = "ai-bert"))]
+    #[test]
+    fn feature_analyzers_are_skipped_when_not_compiled_in() {
+        let config = AnalyzerConfig::default();
+        assert!(!AnalyzerRegistry::available().contains(&"sentiment"));
+        let registry = AnalyzerRegistry::from_spec("heuristics,sentiment:0.5", "sum", &config).unwrap();
+        assert_eq!(registry.names(), ["heuristics"]);
+        assert_eq!(AnalyzerRegistry::from_spec("sentiment", "sum", &config).err().unwrap(), "No analyzers enabled");
+    }
 }
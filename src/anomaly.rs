@@ -0,0 +1,351 @@
+use crate::ai_engine::{done, Analysis, Analyzer, AnalyzerFuture};
+use crate::prober::ProbeResult;
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// Responses a host must have given before anything counts as unusual
+const MIN_SAMPLES: usize = 8;
+
+// Standard deviations from the host mean that make a length, word count or time an outlier
+const Z_THRESHOLD: f64 = 4.0;
+
+// A status or header seen in at most this share of a host's responses is rare
+const RARE_SHARE: f64 = 0.05;
+
+// How much a certain anomaly (score 1.0) adds to the AI score
+const ANOMALY_WEIGHT: f32 = 0.4;
+
+// Measured per response: name, then the smallest spread taken into account
+// (share of the typical value plus a minimum) and whether only higher values count.
+// Sizes count in both directions; time only when slower (delays hint at server-side work).
+const METRICS: [(&str, f64, f64, bool); 3] =
+    [("length", 0.05, 10.0, false), ("words", 0.05, 3.0, false), ("time (ms)", 0.25, 50.0, true)];
+
+// Headers whose presence says nothing about the page
+const IGNORED_HEADERS: [&str; 8] =
+    ["date", "content-length", "age", "expires", "etag", "last-modified", "x-request-id", "connection"];
+
+// How unusual a result is for its host, with what made it stand out
+#[derive(Debug, Clone, Serialize)]
+pub struct Anomaly {
+    pub score: f32,  // 0.0-1.0
+    pub reasons: Vec<String>,
+}
+
+impl Anomaly {
+    fn insight(&self) -> String {
+        format!("Anomalous for this host ({:.2}): {}", self.score, self.reasons.join(", "))
+    }
+}
+
+// Where a measurement usually lies and how far it usually strays
+#[derive(Debug, Clone, Copy)]
+struct Spread {
+    center: f64,
+    std: f64,
+}
+
+impl Spread {
+    // Deviation in standard deviations; the floor keeps a scan of identical
+    // responses from flagging every small difference
+    fn z(&self, x: f64, floor: f64) -> f64 {
+        (x - self.center) / self.std.max(floor)
+    }
+
+    // Median and scaled median absolute deviation: unlike mean and standard
+    // deviation, one huge outlier cannot hide the others
+    fn robust(values: &[f64]) -> Spread {
+        let median = |v: &mut Vec<f64>| {
+            v.sort_by(f64::total_cmp);
+            let mid = v.len() / 2;
+            if v.is_empty() { 0.0 } else if v.len().is_multiple_of(2) { (v[mid - 1] + v[mid]) / 2.0 } else { v[mid] }
+        };
+        let center = median(&mut values.to_vec());
+        let mad = median(&mut values.iter().map(|x| (x - center).abs()).collect());
+        Spread { center, std: mad * 1.4826 }
+    }
+}
+
+// Running mean and variance (Welford), for judging during the scan
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    n: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Stats {
+    fn push(&mut self, x: f64) {
+        self.n += 1.0;
+        let delta = x - self.mean;
+        self.mean += delta / self.n;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn spread(&self) -> Spread {
+        Spread { center: self.mean, std: if self.n > 0.0 { (self.m2 / self.n).sqrt() } else { 0.0 } }
+    }
+}
+
+// The measurements taken from one response
+struct Sample {
+    status: u16,
+    headers: Vec<String>,  // Lowercased names, deduplicated
+    metrics: [f64; 3],     // In METRICS order
+}
+
+impl Sample {
+    fn of(result: &ProbeResult) -> Sample {
+        let mut headers: Vec<String> = result
+            .headers
+            .iter()
+            .map(|(name, _)| name.to_lowercase())
+            .filter(|name| !IGNORED_HEADERS.contains(&name.as_str()))
+            .collect();
+        headers.sort();
+        headers.dedup();
+        Sample { status: result.status, headers, metrics: [result.body_len as f64, result.words as f64, result.elapsed_ms as f64] }
+    }
+}
+
+// What one host's responses usually look like
+#[derive(Debug, Clone, Default)]
+struct Baseline {
+    count: usize,
+    statuses: HashMap<u16, usize>,
+    headers: HashMap<String, usize>,  // Responses carrying each header
+    stats: [Stats; 3],                // Per metric, as the scan goes
+    values: [Vec<f64>; 3],            // Per metric, for the post-scan pass
+}
+
+impl Baseline {
+    fn push(&mut self, sample: &Sample) {
+        self.count += 1;
+        *self.statuses.entry(sample.status).or_default() += 1;
+        for name in &sample.headers {
+            *self.headers.entry(name.clone()).or_default() += 1;
+        }
+        for (i, &value) in sample.metrics.iter().enumerate() {
+            self.stats[i].push(value);
+            self.values[i].push(value);
+        }
+    }
+
+    fn rolling_spreads(&self) -> [Spread; 3] {
+        self.stats.map(|s| s.spread())
+    }
+
+    fn robust_spreads(&self) -> [Spread; 3] {
+        [0, 1, 2].map(|i| Spread::robust(&self.values[i]))
+    }
+
+    // Counts as they would be without this (already pushed) sample; the
+    // robust spreads barely move for one sample, so they are left as they are
+    fn without(&self, sample: &Sample) -> Baseline {
+        let mut rest = Baseline {
+            count: self.count.saturating_sub(1),
+            statuses: self.statuses.clone(),
+            headers: self.headers.clone(),
+            ..Default::default()
+        };
+        if let Some(n) = rest.statuses.get_mut(&sample.status) {
+            *n = n.saturating_sub(1);
+        }
+        for name in &sample.headers {
+            if let Some(n) = rest.headers.get_mut(name) {
+                *n = n.saturating_sub(1);
+            }
+        }
+        rest
+    }
+
+    // None until the host has enough responses, or when nothing stands out
+    fn judge(&self, sample: &Sample, spreads: &[Spread; 3]) -> Option<Anomaly> {
+        if self.count < MIN_SAMPLES {
+            return None;
+        }
+        let count = self.count as f64;
+        let mut signals: Vec<(f64, String)> = vec![];  // (strength 0-1, reason)
+
+        // A status or a header almost never seen on this host
+        let rarity = |seen: usize| -> Option<f64> {
+            let share = seen as f64 / count;
+            (share <= RARE_SHARE).then(|| 1.0 - 0.5 * share / RARE_SHARE)
+        };
+        let status_seen = self.statuses.get(&sample.status).copied().unwrap_or(0);
+        if let Some(strength) = rarity(status_seen) {
+            signals.push((strength, format!("rare status {} ({} of {})", sample.status, status_seen, self.count)));
+        }
+        let rare_headers: Vec<&str> = sample
+            .headers
+            .iter()
+            .filter(|name| rarity(self.headers.get(*name).copied().unwrap_or(0)).is_some())
+            .map(String::as_str)
+            .collect();
+        if !rare_headers.is_empty() {
+            signals.push((0.5, format!("rare header(s) {}", rare_headers.join(", "))));
+        }
+
+        for ((name, floor_share, floor_min, higher_only), (&value, spread)) in
+            METRICS.iter().zip(sample.metrics.iter().zip(spreads))
+        {
+            let z = spread.z(value, spread.center * floor_share + floor_min);
+            let deviation = if *higher_only { z } else { z.abs() };
+            if deviation >= Z_THRESHOLD {
+                signals.push((
+                    (deviation / (2.0 * Z_THRESHOLD)).min(1.0),
+                    format!("{} {} vs ~{:.0} (z {:.1})", name, value, spread.center, z),
+                ));
+            }
+        }
+
+        if signals.is_empty() {
+            return None;
+        }
+        let score = 1.0 - signals.iter().map(|(s, _)| 1.0 - s).product::<f64>();
+        Some(Anomaly { score: score as f32, reasons: signals.into_iter().map(|(_, r)| r).collect() })
+    }
+}
+
+fn host_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(u) => format!("{}:{}", u.host_str().unwrap_or(""), u.port_or_known_default().unwrap_or(0)),
+        Err(_) => String::new(),
+    }
+}
+
+// Flags responses that look different from the rest of their host's:
+// rare statuses or headers, and lengths, word counts or timings far from the
+// host's mean. During the scan each result is compared with the responses
+// before it; after the scan every result is judged again against all the others.
+#[derive(Default)]
+pub struct AnomalyDetector {
+    baselines: Mutex<HashMap<String, Baseline>>,
+}
+
+impl AnomalyDetector {
+    fn record(analysis: &mut Analysis, anomaly: Option<Anomaly>) {
+        if let Some(anomaly) = &anomaly {
+            analysis.add("anomaly", "outlier", anomaly.score * ANOMALY_WEIGHT, anomaly.insight());
+        }
+        analysis.anomaly = anomaly;
+    }
+}
+
+impl Analyzer for AnomalyDetector {
+    fn name(&self) -> &'static str {
+        "anomaly"
+    }
+
+    fn analyze<'a>(&'a self, result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a> {
+        let sample = Sample::of(result);
+        let anomaly = {
+            let mut baselines = self.baselines.lock().unwrap();
+            let baseline = baselines.entry(host_key(&result.url)).or_default();
+            let anomaly = baseline.judge(&sample, &baseline.rolling_spreads());
+            baseline.push(&sample);
+            anomaly
+        };
+        AnomalyDetector::record(analysis, anomaly);
+        done()
+    }
+
+    fn finish(&self, results: &mut [(&ProbeResult, &mut Analysis)]) {
+        let baselines = self.baselines.lock().unwrap();
+        let spreads: HashMap<&String, [Spread; 3]> = baselines.iter().map(|(host, b)| (host, b.robust_spreads())).collect();
+        for (result, analysis) in results.iter_mut() {
+            // Replace the rolling verdict with one against the whole scan
+            if let Some(old) = analysis.anomaly.take() {
+                let insight = old.insight();
+                analysis.insights.retain(|i| *i != insight);
+                analysis.components.retain(|c| c.analyzer != self.name());
+            }
+            let sample = Sample::of(result);
+            let host = host_key(&result.url);
+            let anomaly = baselines.get(&host).and_then(|b| b.without(&sample).judge(&sample, &spreads[&host]));
+            AnomalyDetector::record(analysis, anomaly);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(status: u16, headers: &[&str], length: f64, time: f64) -> Sample {
+        Sample { status, headers: headers.iter().map(|h| h.to_string()).collect(), metrics: [length, length / 5.0, time] }
+    }
+
+    fn host(samples: usize) -> Baseline {
+        let mut baseline = Baseline::default();
+        for i in 0..samples {
+            baseline.push(&sample(404, &["server", "content-type"], 1200.0 + (i % 3) as f64, 40.0 + (i % 5) as f64));
+        }
+        baseline
+    }
+
+    #[test]
+    fn robust_spread_ignores_a_single_outlier() {
+        let spread = Spread::robust(&[10.0, 12.0, 11.0, 13.0, 9.0, 100_000.0]);
+        assert_eq!(spread.center, 11.5);
+        assert!((spread.std - 1.5 * 1.4826).abs() < 1e-9);
+        assert_eq!(Spread::robust(&[5.0, 1.0, 3.0]).center, 3.0);
+        let empty = Spread::robust(&[]);
+        assert_eq!((empty.center, empty.std), (0.0, 0.0));
+        // Identical values: the floor stands in for the zero spread
+        assert_eq!(Spread::robust(&[7.0; 4]).z(17.0, 2.0), 5.0);
+    }
+
+    #[test]
+    fn rolling_stats_match_mean_and_deviation() {
+        let mut stats = Stats::default();
+        for x in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            stats.push(x);
+        }
+        let spread = stats.spread();
+        assert_eq!((spread.center, spread.std), (5.0, 2.0));
+    }
+
+    #[test]
+    fn nothing_is_judged_before_enough_samples() {
+        let baseline = host(MIN_SAMPLES - 1);
+        let odd = sample(200, &["server", "x-debug"], 90_000.0, 40.0);
+        assert!(baseline.judge(&odd, &baseline.robust_spreads()).is_none());
+    }
+
+    #[test]
+    fn typical_responses_are_not_anomalies() {
+        let baseline = host(20);
+        let usual = sample(404, &["server", "content-type"], 1201.0, 45.0);
+        assert!(baseline.judge(&usual, &baseline.robust_spreads()).is_none());
+        // Faster than usual is not suspicious
+        let fast = sample(404, &["server", "content-type"], 1201.0, 1.0);
+        assert!(baseline.judge(&fast, &baseline.robust_spreads()).is_none());
+    }
+
+    #[test]
+    fn outliers_give_reasons() {
+        let baseline = host(40);
+        let odd = sample(200, &["server", "content-type", "x-debug"], 25_000.0, 2_000.0);
+        let anomaly = baseline.judge(&odd, &baseline.robust_spreads()).unwrap();
+        let reasons = anomaly.reasons.join("; ");
+        for expected in ["rare status 200 (0 of 40)", "rare header(s) x-debug", "length 25000", "words 5000", "time (ms) 2000"] {
+            assert!(reasons.contains(expected), "{}", reasons);
+        }
+        assert!(anomaly.score > 0.99 && anomaly.score <= 1.0);
+    }
+
+    #[test]
+    fn without_removes_the_sample_from_the_counts() {
+        let mut baseline = host(20);
+        let odd = sample(500, &["server"], 1200.0, 40.0);
+        baseline.push(&odd);
+        let rest = baseline.without(&odd);
+        assert_eq!(rest.count, 20);
+        assert_eq!(rest.statuses[&500], 0);
+        assert_eq!(rest.headers["server"], 20);
+        assert!(rest.judge(&odd, &baseline.robust_spreads()).unwrap().reasons[0].starts_with("rare status 500"));
+    }
+}
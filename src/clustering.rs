@@ -0,0 +1,210 @@
+use crate::prober::ProbeResult;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+
+// Maximum differing simhash bits (of 64) for two pages to share a cluster
+const CONTENT_DISTANCE: u32 = 4;
+const STRUCTURE_DISTANCE: u32 = 6;
+
+// Words per content shingle and tags per structure shingle
+const WORD_SHINGLE: usize = 3;
+const TAG_SHINGLE: usize = 4;
+
+lazy_static! {
+    static ref SCRIPT_STYLE_RE: Regex = Regex::new(r"(?is)<script\b.*?</script>|<style\b.*?</style>").unwrap();
+    static ref TAG_RE: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref TAG_NAME_RE: Regex = Regex::new(r"<\s*(/?[a-zA-Z][a-zA-Z0-9-]*)").unwrap();
+}
+
+// Where a result landed: cluster ID (1-based, in order of first appearance),
+// cluster size, and whether it is the cluster's representative
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterInfo {
+    pub id: usize,
+    pub size: usize,
+    pub representative: bool,
+}
+
+// Similarity features of one response
+#[derive(Debug)]
+pub struct PageSignature {
+    status: u16,
+    content: u64,          // Simhash of normalised visible text
+    structure: u64,        // Simhash of the HTML tag sequence
+    exact: Option<String>,  // Binary bodies only cluster on identical bytes
+}
+
+// FNV-1a: a stable hash, so signatures don't change between runs
+fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+// Charikar simhash: each feature votes on every bit; near-duplicate
+// feature sets give hashes a few bits apart
+fn simhash(features: &[String]) -> u64 {
+    let mut votes = [0i32; 64];
+    for feature in features {
+        let h = fnv1a(feature.as_bytes());
+        for (bit, vote) in votes.iter_mut().enumerate() {
+            *vote += if h >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+    votes.iter().enumerate().filter(|(_, v)| **v > 0).fold(0, |acc, (bit, _)| acc | 1 << bit)
+}
+
+// Overlapping n-grams, or the items themselves when there are fewer than n
+fn shingles(items: &[String], n: usize) -> Vec<String> {
+    if items.len() < n {
+        return items.to_vec();
+    }
+    items.windows(n).map(|w| w.join(" ")).collect()
+}
+
+// Visible words with request-specific noise removed: the requested path
+// (error pages echo it), and numbers/IDs collapsed to one placeholder
+pub fn content_tokens(result: &ProbeResult, body: &str) -> Vec<String> {
+    let mut text = SCRIPT_STYLE_RE.replace_all(body, " ").into_owned();
+    text = TAG_RE.replace_all(&text, " ").into_owned();
+    if let Ok(url) = Url::parse(&result.url) {
+        let path = url.path().to_string();
+        let last = path.rsplit('/').find(|s| !s.is_empty()).unwrap_or("").to_string();
+        for echoed in [result.url.as_str(), path.as_str(), last.as_str()] {
+            if echoed.len() > 2 {
+                text = text.replace(echoed, " ");
+            }
+        }
+    }
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| if w.chars().any(|c| c.is_ascii_digit()) { "0".to_string() } else { w.to_lowercase() })
+        .collect()
+}
+
+pub fn signature(result: &ProbeResult) -> PageSignature {
+    if result.is_binary() {
+        return PageSignature { status: result.status, content: 0, structure: 0, exact: result.body_sha256.clone() };
+    }
+    let body = result.body_text();
+    let tags: Vec<String> = TAG_NAME_RE.captures_iter(&body).map(|c| c[1].to_lowercase()).collect();
+    PageSignature {
+        status: result.status,
+        content: simhash(&shingles(&content_tokens(result, &body), WORD_SHINGLE)),
+        structure: simhash(&shingles(&tags, TAG_SHINGLE)),
+        exact: None,
+    }
+}
+
+pub fn similar(a: &PageSignature, b: &PageSignature) -> bool {
+    if a.status != b.status {
+        return false;
+    }
+    match (&a.exact, &b.exact) {
+        (None, None) => {
+            (a.content ^ b.content).count_ones() <= CONTENT_DISTANCE
+                && (a.structure ^ b.structure).count_ones() <= STRUCTURE_DISTANCE
+        }
+        (x, y) => x == y,
+    }
+}
+
+// Groups near-identical responses by their signatures; returns one
+// ClusterInfo per input, in order. Leader clustering: each result joins the
+// first cluster whose representative (its first member) is similar enough,
+// otherwise it starts a new cluster.
+pub fn cluster(signatures: &[PageSignature]) -> Vec<ClusterInfo> {
+    let mut leaders: Vec<usize> = vec![];  // Index of each cluster's representative
+    let mut assigned: Vec<usize> = Vec::with_capacity(signatures.len());
+    for (i, sig) in signatures.iter().enumerate() {
+        match leaders.iter().position(|&l| similar(&signatures[l], sig)) {
+            Some(c) => assigned.push(c),
+            None => {
+                leaders.push(i);
+                assigned.push(leaders.len() - 1);
+            }
+        }
+    }
+
+    let mut sizes = vec![0; leaders.len()];
+    for &c in &assigned {
+        sizes[c] += 1;
+    }
+    assigned
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| ClusterInfo { id: c + 1, size: sizes[c], representative: leaders[c] == i })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+
+    fn page(url: &str, status: u16, content_type: &str, body: &[u8]) -> ProbeResult {
+        test_response(url, status, &[("content-type", content_type)], body)
+    }
+
+    fn not_found(path: &str, request_id: u32) -> ProbeResult {
+        let body = format!(
+            "<html><head><title>Not Found</title><script>var t = {};</script></head><body><h1>Not Found</h1>\
+             <p>The requested URL /{} was not found on this server.</p><p>Request ID {}</p></body></html>",
+            request_id, path, request_id
+        );
+        page(&format!("http://t/{}", path), 404, "text/html", body.as_bytes())
+    }
+
+    #[test]
+    fn simhash_distance_follows_overlap() {
+        let words = |s: &str| shingles(&s.split(' ').map(String::from).collect::<Vec<_>>(), WORD_SHINGLE);
+        let a = simhash(&words("the quick brown fox jumps over the lazy dog near the river bank today"));
+        assert_eq!(a, simhash(&words("the quick brown fox jumps over the lazy dog near the river bank today")));
+        let b = simhash(&words("an entirely different sentence about login forms and session cookies here"));
+        assert!((a ^ b).count_ones() > CONTENT_DISTANCE);
+        assert_eq!(simhash(&[]), 0);
+    }
+
+    #[test]
+    fn tokens_drop_the_echoed_path_and_numbers() {
+        let result = not_found("backup-2024", 9812);
+        let tokens = content_tokens(&result, &result.body_text());
+        assert!(!tokens.iter().any(|t| t.contains("backup")));
+        assert!(!tokens.contains(&"9812".to_string()) && tokens.contains(&"0".to_string()));
+        assert!(!tokens.contains(&"var".to_string()));
+        assert_eq!(&tokens[..2], ["not", "found"]);
+    }
+
+    #[test]
+    fn error_pages_for_different_paths_cluster_together() {
+        let pages = [
+            not_found("admin", 1),
+            not_found("old-site.zip", 22222),
+            page("http://t/", 200, "text/html", b"<html><body><h1>Welcome</h1><p>Company home page with news</p></body></html>"),
+            not_found("wp-admin", 3),
+        ];
+        let signatures: Vec<PageSignature> = pages.iter().map(signature).collect();
+        assert!(similar(&signatures[0], &signatures[1]));
+        assert!(!similar(&signatures[0], &signatures[2]));
+        let clusters = cluster(&signatures);
+        let ids: Vec<usize> = clusters.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [1, 1, 2, 1]);
+        assert_eq!(clusters[0].size, 3);
+        assert!(clusters[0].representative && !clusters[1].representative && clusters[2].representative);
+    }
+
+    #[test]
+    fn binary_bodies_only_match_identical_bytes() {
+        let a = signature(&page("http://t/a.bin", 200, "application/octet-stream", &[0, 1, 2, 3]));
+        let b = signature(&page("http://t/b.bin", 200, "application/octet-stream", &[0, 1, 2, 3]));
+        let c = signature(&page("http://t/c.bin", 200, "application/octet-stream", &[0, 1, 2, 4]));
+        assert!(similar(&a, &b) && !similar(&a, &c));
+        let d = signature(&page("http://t/d.bin", 404, "application/octet-stream", &[0, 1, 2, 3]));
+        assert!(!similar(&a, &d));
+    }
+}
@@ -0,0 +1,355 @@
+use crate::js_extract;
+use crate::prober::ProbeResult;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+// Files that never link anywhere; fetching them only spends the page budget
+const STATIC_EXTENSIONS: [&str; 20] = [
+    "png", "jpg", "jpeg", "gif", "svg", "ico", "webp", "bmp", "css", "woff", "woff2", "ttf", "eot", "otf", "mp3", "mp4",
+    "webm", "pdf", "zip", "gz",
+];
+
+lazy_static! {
+    static ref SITEMAP_LOC_RE: Regex = Regex::new(r"(?i)<loc>\s*([^<\s]+)\s*</loc>").unwrap();
+}
+
+// Something a page points at: a URL to request, or a form to know about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Endpoint {
+    pub method: String,       // GET for links and GET forms; POST forms are recorded, never submitted
+    pub url: String,          // With the query string for GET, as found
+    pub params: Vec<String>,  // Query and form field names
+}
+
+// Which URLs a crawl may request: same scheme, host and port as the start
+pub struct CrawlScope {
+    start: Url,
+}
+
+impl CrawlScope {
+    fn in_scope(&self, url: &Url) -> bool {
+        url.origin() == self.start.origin()
+    }
+
+    // Endpoints referenced by a response: links, scripts, forms, paths and
+    // URLs in JavaScript, robots.txt rules and sitemap entries
+    pub fn extract(&self, result: &ProbeResult) -> Vec<Endpoint> {
+        let Ok(page) = Url::parse(&result.url) else { return vec![] };
+        if result.is_binary() || !(200..400).contains(&result.status) {
+            return vec![];
+        }
+        let body = result.body_text();
+        let mut found: Vec<Endpoint> = vec![];
+        let mut link = |raw: &str| {
+            if let Some(url) = self.resolve(&page, raw) {
+                found.push(Endpoint { method: "GET".to_string(), params: query_names(&url), url: url.to_string() });
+            }
+        };
+
+        if let Some(html) = &result.html {
+            html.links.iter().chain(&html.scripts).for_each(|raw| link(raw));
+        }
+        if page.path() == "/robots.txt" {
+            for line in body.lines() {
+                let (key, value) = line.split_once(':').unwrap_or(("", ""));
+                match key.trim().to_lowercase().as_str() {
+                    // Wildcards end the usable prefix: "Disallow: /admin/*.php" -> /admin/
+                    "allow" | "disallow" => {
+                        let path = value.trim().split(['*', '$']).next().unwrap_or("");
+                        if path.starts_with('/') && path.len() > 1 {
+                            link(path);
+                        }
+                    }
+                    "sitemap" => link(value.trim()),
+                    _ => {}
+                }
+            }
+        } else if body.contains("<urlset") || body.contains("<sitemapindex") {
+            for caps in SITEMAP_LOC_RE.captures_iter(&body) {
+                link(&caps[1]);
+            }
+        } else {
+            // Paths and URLs in scripts: inline ones for HTML, the body for JS, JSON and other text
+            let scripts = match &result.html {
+                Some(html) => html.inline_scripts.clone(),
+                None => vec![body.into_owned()],
+            };
+            for script in &scripts {
+                for endpoint in js_extract::endpoints_in(script, &page) {
+                    // Template placeholders ("/users/{id}") are recorded by --js-extract, not requested
+                    if !endpoint.value.contains('{') {
+                        link(&endpoint.value);
+                    }
+                }
+            }
+        }
+
+        // GET forms are followed with their default values; other methods are only recorded
+        if let Some(html) = &result.html {
+            for form in &html.forms {
+                let Some(mut url) = self.resolve(&page, form.action.as_deref().unwrap_or("")) else { continue };
+                let names: Vec<&String> = form.inputs.iter().filter_map(|i| i.name.as_ref()).collect();
+                if form.method == "GET" {
+                    let fields: Vec<(&String, &str)> = form
+                        .inputs
+                        .iter()
+                        .filter_map(|i| i.name.as_ref().map(|n| (n, i.value.as_deref().unwrap_or(""))))
+                        .collect();
+                    if !fields.is_empty() {
+                        url.query_pairs_mut().clear().extend_pairs(fields);
+                    }
+                }
+                let mut params = query_names(&url);
+                for name in names {
+                    if !params.contains(name) {
+                        params.push(name.clone());
+                    }
+                }
+                found.push(Endpoint { method: form.method.clone(), url: url.to_string(), params });
+            }
+        }
+        found
+    }
+
+    // Absolute, in-scope URL without fragment, or None
+    fn resolve(&self, page: &Url, raw: &str) -> Option<Url> {
+        let raw = raw.trim();
+        if raw.starts_with('#') || raw.starts_with("javascript:") || raw.starts_with("mailto:") || raw.starts_with("data:") {
+            return None;
+        }
+        let mut url = page.join(raw).ok()?;
+        url.set_fragment(None);
+        self.in_scope(&url).then_some(url)
+    }
+}
+
+fn query_names(url: &Url) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for (name, _) in url.query_pairs() {
+        if !names.iter().any(|n| *n == name) {
+            names.push(name.into_owned());
+        }
+    }
+    names
+}
+
+fn is_static(url: &Url) -> bool {
+    let last = url.path().rsplit('/').next().unwrap_or("");
+    last.rsplit_once('.').is_some_and(|(_, ext)| STATIC_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// Breadth-first crawl state: what was requested, what was found, and the
+// depth and page budget. The scan requests each level, hands the endpoints
+// its responses reference back, and gets the next level.
+pub struct Crawler {
+    scope: Arc<CrawlScope>,
+    max_depth: usize,
+    max_pages: usize,
+    depth: usize,
+    requested: HashSet<String>,
+    endpoints: Vec<Endpoint>,
+    endpoint_index: HashMap<(String, String), usize>,  // (method, URL without query) -> endpoints
+}
+
+impl Crawler {
+    pub fn new(start: Url, max_depth: usize, max_pages: usize) -> Crawler {
+        Crawler {
+            scope: Arc::new(CrawlScope { start }),
+            max_depth,
+            max_pages,
+            depth: 0,
+            requested: HashSet::new(),
+            endpoints: vec![],
+            endpoint_index: HashMap::new(),
+        }
+    }
+
+    pub fn scope(&self) -> Arc<CrawlScope> {
+        self.scope.clone()
+    }
+
+    // The start page, robots.txt and sitemap.xml
+    pub fn seeds(&mut self) -> Vec<String> {
+        let start = self.scope.start.clone();
+        let seeds = [start.clone(), start.join("/robots.txt").unwrap(), start.join("/sitemap.xml").unwrap()];
+        seeds.into_iter().filter_map(|url| self.request(url.to_string())).collect()
+    }
+
+    fn request(&mut self, url: String) -> Option<String> {
+        (self.requested.len() < self.max_pages && self.requested.insert(url.clone())).then_some(url)
+    }
+
+    // Records what the last level referenced; returns the URLs of the next
+    // level (empty once the depth or page budget is used up)
+    pub fn next_level(&mut self, found: Vec<Endpoint>) -> Vec<String> {
+        let mut next = vec![];
+        for endpoint in found {
+            let Ok(url) = Url::parse(&endpoint.url) else { continue };
+            if is_static(&url) {
+                continue;
+            }
+            let mut bare = url.clone();
+            bare.set_query(None);
+            match self.endpoint_index.get(&(endpoint.method.clone(), bare.to_string())) {
+                Some(&i) => {
+                    let known = &mut self.endpoints[i];
+                    for param in endpoint.params.iter() {
+                        if !known.params.contains(param) {
+                            known.params.push(param.clone());
+                        }
+                    }
+                }
+                None => {
+                    self.endpoint_index.insert((endpoint.method.clone(), bare.to_string()), self.endpoints.len());
+                    self.endpoints.push(Endpoint { url: bare.to_string(), ..endpoint.clone() });
+                }
+            }
+            if endpoint.method == "GET" && self.depth < self.max_depth {
+                if let Some(url) = self.request(endpoint.url) {
+                    next.push(url);
+                }
+            }
+        }
+        self.depth += 1;
+        next
+    }
+
+    // Levels requested so far (the seeds are depth 0)
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn was_requested(&self, url: &str) -> bool {
+        self.requested.contains(url)
+    }
+
+    pub fn pages(&self) -> usize {
+        self.requested.len()
+    }
+
+    pub fn endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    // Directories the crawl saw, in discovery order, for fuzzing under
+    // (e.g. /admin/ and /api/v1/ from /api/v1/users); the start directory is left out
+    pub fn directories(&self) -> Vec<String> {
+        let start = self.scope.start.join("./").map(|u| u.to_string()).unwrap_or_default();
+        let mut dirs: Vec<String> = vec![];
+        for endpoint in &self.endpoints {
+            let Ok(url) = Url::parse(&endpoint.url) else { continue };
+            let segments: Vec<&str> = url.path().split('/').filter(|s| !s.is_empty()).collect();
+            let dir_segments = if url.path().ends_with('/') { segments.len() } else { segments.len().saturating_sub(1) };
+            for depth in 1..=dir_segments {
+                let dir = format!("{}/", segments[..depth].join("/"));
+                let Ok(dir) = url.join(&format!("/{}", dir)) else { continue };
+                let dir = dir.to_string();
+                if dir != start && !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+        }
+        dirs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+
+    fn response(url: &str, content_type: &str, body: &str) -> ProbeResult {
+        test_response(url, 200, &[("content-type", content_type)], body)
+    }
+
+    fn crawler() -> Crawler {
+        Crawler::new(Url::parse("http://t/app/").unwrap(), 2, 10)
+    }
+
+    fn urls(endpoints: &[Endpoint]) -> Vec<&str> {
+        endpoints.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    #[test]
+    fn html_links_scripts_and_forms() {
+        let page = r#"<html><body>
+            <a href="docs/intro#top">Docs</a> <a href="https://elsewhere.example/">Out</a>
+            <a href="javascript:void(0)">JS</a> <a href="mailto:a@t">Mail</a>
+            <script src="/static/main.js"></script>
+            <script>fetch("/api/v1/users?page=2"); fetch(`/api/v1/users/${id}`);</script>
+            <form action="/search"><input name="q" value="test"><input name="lang"></form>
+            <form method="post" action="/login"><input name="user"><input name="pass" type="password"></form>
+        </body></html>"#;
+        let found = crawler().scope().extract(&response("http://t/app/", "text/html", page));
+        assert_eq!(
+            urls(&found),
+            [
+                "http://t/app/docs/intro",
+                "http://t/static/main.js",
+                "http://t/api/v1/users?page=2",
+                "http://t/search?q=test&lang=",
+                "http://t/login"
+            ]
+        );
+        assert_eq!(found[2].params, ["page"]);
+        assert_eq!((found[3].method.as_str(), &found[3].params[..]), ("GET", &["q".to_string(), "lang".to_string()][..]));
+        assert_eq!((found[4].method.as_str(), &found[4].params[..]), ("POST", &["user".to_string(), "pass".to_string()][..]));
+    }
+
+    #[test]
+    fn robots_and_sitemaps() {
+        let scope = crawler().scope();
+        let robots = "User-agent: *\nDisallow: /admin/*.php\nDisallow: /\nAllow: /public$\nSitemap: http://t/sitemap-main.xml\n";
+        let found = scope.extract(&response("http://t/robots.txt", "text/plain", robots));
+        assert_eq!(urls(&found), ["http://t/admin/", "http://t/public", "http://t/sitemap-main.xml"]);
+        let sitemap = "<?xml version=\"1.0\"?><urlset><url><loc> http://t/a </loc></url><url><loc>http://x/b</loc></url></urlset>";
+        let found = scope.extract(&response("http://t/sitemap.xml", "application/xml", sitemap));
+        assert_eq!(urls(&found), ["http://t/a"]);
+    }
+
+    #[test]
+    fn levels_respect_depth_budget_and_static_files() {
+        let mut crawler = crawler();
+        assert_eq!(crawler.seeds(), ["http://t/app/", "http://t/robots.txt", "http://t/sitemap.xml"]);
+        let get = |url: &str, params: &[&str]| Endpoint {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            params: params.iter().map(|p| p.to_string()).collect(),
+        };
+        let level = crawler.next_level(vec![
+            get("http://t/api/items?id=1", &["id"]),
+            get("http://t/api/items?sort=asc", &["sort"]),
+            get("http://t/logo.PNG", &[]),
+            get("http://t/app/", &[]),
+        ]);
+        assert_eq!(level, ["http://t/api/items?id=1", "http://t/api/items?sort=asc"]);
+        assert_eq!(crawler.endpoints().len(), 2);
+        assert_eq!(crawler.endpoints()[0].params, ["id", "sort"]);
+        assert_eq!(crawler.next_level(vec![get("http://t/deeper", &[])]), ["http://t/deeper"]);
+        // Depth 2 reached: still recorded, no longer requested
+        assert!(crawler.next_level(vec![get("http://t/deepest", &[])]).is_empty());
+        assert_eq!(crawler.endpoints().len(), 4);
+        assert_eq!(crawler.pages(), 6);
+    }
+
+    #[test]
+    fn page_budget_caps_requests() {
+        let mut crawler = Crawler::new(Url::parse("http://t/").unwrap(), 5, 4);
+        crawler.seeds();
+        let found = ["http://t/a", "http://t/b", "http://t/c"].map(|u| Endpoint { method: "GET".to_string(), url: u.to_string(), params: vec![] });
+        assert_eq!(crawler.next_level(found.to_vec()), ["http://t/a"]);
+        assert!(crawler.was_requested("http://t/a") && !crawler.was_requested("http://t/b"));
+    }
+
+    #[test]
+    fn directories_leave_out_the_start() {
+        let mut crawler = crawler();
+        let get = |url: &str| Endpoint { method: "GET".to_string(), url: url.to_string(), params: vec![] };
+        crawler.next_level(vec![get("http://t/api/v1/users"), get("http://t/app/settings"), get("http://t/admin/"), get("http://t/api/v1/orders")]);
+        assert_eq!(crawler.directories(), ["http://t/api/", "http://t/api/v1/", "http://t/admin/"]);
+    }
+}
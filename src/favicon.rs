@@ -0,0 +1,181 @@
+use crate::html_parser::HtmlInfo;
+use crate::prober::probe_url;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use md5::{Digest, Md5};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+
+// Favicons larger than this are not real icons; skip them
+const MAX_FAVICON_SIZE: usize = 1024 * 1024;
+
+// Hashes of a host's favicon in the formats used by Shodan and favicon databases
+#[derive(Debug, Clone, Serialize)]
+pub struct FaviconHash {
+    pub url: String,
+    pub mmh3: i32,    // Shodan http.favicon.hash
+    pub md5: String,  // OWASP/Wappalyzer-style favicon databases
+}
+
+// Lazily-filled favicon result for one origin
+type FaviconSlot = Arc<OnceCell<Option<FaviconHash>>>;
+
+// Caches one favicon lookup per origin so each host is fetched at most once
+#[derive(Clone)]
+pub struct FaviconCache {
+    headers: Vec<(String, String)>,  // The scan's -H headers, sent with every favicon request
+    permits: Arc<Semaphore>,         // The scan's rate limit, one permit per favicon request
+    hosts: Arc<Mutex<HashMap<String, FaviconSlot>>>,
+}
+
+impl FaviconCache {
+    pub fn new(headers: Vec<(String, String)>, permits: Arc<Semaphore>) -> FaviconCache {
+        FaviconCache { headers, permits, hosts: Arc::default() }
+    }
+
+    // Returns the favicon for the page's origin, fetching it on first use
+    pub async fn get(&self, client: &Client, page_url: &str, html: Option<&HtmlInfo>) -> Option<FaviconHash> {
+        let page = Url::parse(page_url).ok()?;
+        let origin = page.origin().ascii_serialization();
+        let cell = self.hosts.lock().unwrap().entry(origin).or_default().clone();
+        cell.get_or_init(|| fetch_favicon(client, &page, html, &self.headers, &self.permits)).await.clone()
+    }
+}
+
+// MurmurHash3 x86_32 (seed 0), as used by Python's mmh3.hash
+fn murmur3_32(data: &[u8]) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+    let mut h: u32 = 0;
+
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        let mut k: u32 = 0;
+        for (i, &b) in tail.iter().enumerate() {
+            k |= (b as u32) << (8 * i);
+        }
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
+// Shodan-compatible hash: mmh3 over Python's base64.encodebytes output
+// (76-character lines, each terminated by a newline)
+pub fn shodan_hash(bytes: &[u8]) -> i32 {
+    let encoded = BASE64.encode(bytes);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for line in encoded.as_bytes().chunks(76) {
+        wrapped.push_str(std::str::from_utf8(line).unwrap());
+        wrapped.push('\n');
+    }
+    murmur3_32(wrapped.as_bytes()) as i32
+}
+
+pub fn hash_favicon(url: String, bytes: &[u8]) -> FaviconHash {
+    FaviconHash {
+        url,
+        mmh3: shodan_hash(bytes),
+        md5: format!("{:x}", Md5::digest(bytes)),
+    }
+}
+
+// Fetches the icon declared by <link rel=icon>, falling back to /favicon.ico.
+// The body is streamed with the size cap, so a huge "icon" is never buffered.
+async fn fetch_favicon(
+    client: &Client,
+    page: &Url,
+    html: Option<&HtmlInfo>,
+    headers: &[(String, String)],
+    permits: &Semaphore,
+) -> Option<FaviconHash> {
+    let mut candidates: Vec<Url> = html
+        .map(|h| h.icons.iter().filter_map(|href| page.join(href).ok()).collect())
+        .unwrap_or_default();
+    if let Ok(default_icon) = page.join("/favicon.ico") {
+        if !candidates.contains(&default_icon) {
+            candidates.push(default_icon);
+        }
+    }
+
+    for icon_url in candidates {
+        let permit = permits.acquire().await.ok()?;
+        let res = probe_url(icon_url.to_string(), client, "GET", None, headers.to_vec(), MAX_FAVICON_SIZE).await;
+        drop(permit);
+        let res = match res {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        // Soft-404 pages often come back as 200 text/html; they are not icons
+        let is_html = res.headers.get("content-type").is_some_and(|ct| ct.to_lowercase().contains("html"));
+        if !(200..300).contains(&res.status) || is_html || res.body.is_empty() || res.body_truncated {
+            continue;
+        }
+        return Some(hash_favicon(icon_url.to_string(), &res.body));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn murmur3_matches_reference_vectors() {
+        assert_eq!(murmur3_32(b""), 0);
+        assert_eq!(murmur3_32(b"hello"), 0x248b_fa47);
+        assert_eq!(murmur3_32(b"Hello, world!"), 0xc036_3e43);
+        assert_eq!(murmur3_32(b"The quick brown fox jumps over the lazy dog"), 0x2e4f_f723);
+        // Python: mmh3.hash("foo")
+        assert_eq!(murmur3_32(b"foo") as i32, -156_908_512);
+    }
+
+    #[test]
+    fn shodan_hash_wraps_base64_like_encodebytes() {
+        assert_eq!(shodan_hash(b"hi"), murmur3_32(b"aGk=\n") as i32);
+        // 57 bytes fill one 76-character line exactly; one more starts a second line
+        let full = BASE64.encode([7u8; 57]);
+        assert_eq!(shodan_hash(&[7u8; 57]), murmur3_32(format!("{}\n", full).as_bytes()) as i32);
+        let encoded = BASE64.encode([7u8; 58]);
+        let wrapped = format!("{}\n{}\n", &encoded[..76], &encoded[76..]);
+        assert_eq!(shodan_hash(&[7u8; 58]), murmur3_32(wrapped.as_bytes()) as i32);
+    }
+
+    #[test]
+    fn favicon_hash_has_both_formats() {
+        let hash = hash_favicon("http://t/favicon.ico".to_string(), b"icon");
+        assert_eq!(hash.md5, "baec6461b0d69dde1b861aefbe375d8a");
+        assert_eq!(hash.mmh3, shodan_hash(b"icon"));
+    }
+
+    #[tokio::test]
+    async fn favicon_requests_wait_for_a_rate_permit() {
+        let client = Client::builder().no_proxy().build().unwrap();
+        let permits = Arc::new(Semaphore::new(0));
+        let cache = FaviconCache::new(vec![], permits.clone());
+        let waiting = tokio::time::timeout(Duration::from_millis(100), cache.get(&client, "http://127.0.0.1:9/", None));
+        assert!(waiting.await.is_err());
+        permits.add_permits(1);
+        let cache = FaviconCache::new(vec![], permits.clone());
+        assert!(cache.get(&client, "http://127.0.0.1:9/", None).await.is_none());
+        assert_eq!(permits.available_permits(), 1);
+    }
+}
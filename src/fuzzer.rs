@@ -1,17 +1,43 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
 use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
 use std::io::{self, BufRead, Write};
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::Semaphore;
 use tokio::task;
-use reqwest::{Client, Proxy as ReqwestProxy};
-use crate::ai_engine::analyze;
-use crate::prober::{probe_url, ProbeResult};
-use crate::tech_fingerprinter::fingerprint;
+use reqwest::{Client, Proxy as ReqwestProxy, Url};
+use crate::active_fingerprint::{load_probes, ActiveFingerprinter};
+use crate::anomaly::Anomaly;
+use crate::ai_engine::{Analysis, AnalyzerRegistry, ScoreComponent};
+use crate::clustering::{self, ClusterInfo, PageSignature};
+use crate::crawler::{Crawler, Endpoint};
+use crate::favicon::{FaviconCache, FaviconHash};
+use crate::headers::{Cookie, Headers};
+use crate::html_parser::HtmlInfo;
+use crate::js_assets::ScriptCache;
+use crate::js_extract::{self, JsFindings};
+use crate::llm_triage::LlmVerdict;
+use crate::page_classifier::{Classification, PageLabel};
+use crate::prober::{probe_url, store_response, ProbeResult};
+use crate::secrets::SecretFinding;
+use crate::signatures::Detection;
+use crate::tech_fingerprinter::{fingerprint, labels, load_signatures};
+use crate::vuln_db::VulnDb;
+use crate::waf_detector::{WafDetection, WafSet};
+use crate::wordlist_gen::WordHarvester;
 use chrono::Local;  // For timestamped filenames (add to Cargo.toml if missing)
 use regex::Regex;
 use serde::Serialize;
-use serde_json;
+
+// Rounds of endpoints found in JavaScript requested in a row (each round's
+// responses may reference more); keeps a chain of generated URLs finite
+const JS_ROUNDS: usize = 3;
+
+// One kept result with its optional AI analysis and tech detections
+type ScanResult = (ProbeResult, Option<Analysis>, Option<Vec<Detection>>);
 
 // Loads words from a file into a Vec<String>
 pub fn load_wordlist(path: &str) -> io::Result<Vec<String>> {
@@ -26,33 +52,17 @@ pub fn generate_urls(base_url: &str, words: &[String]) -> Vec<String> {
     words.iter().map(|word| base_url.replace("FUZZ", word)).collect()
 }
 
-// Main fuzz function: generates URLs, spawns async tasks for probing
-pub async fn fuzz(
-    base_url: String,
-    wordlist_path: String,
-    concurrency: usize,
-    ai_enabled: bool,
-    filter_status: Option<Vec<u16>>,
-    filter_size: Option<Vec<usize>>,
-    rate: usize,
-    output: String,
-    method: String,
-    data: Option<String>,
-    raw_headers: Vec<String>,
-    tech_enabled: bool,
-    proxy_url: Option<String>,
-    proxy_auth: Option<String>,
-    filter_regex: Vec<String>,
-) {
-    let words = match load_wordlist(&wordlist_path) {
-        Ok(w) => w,
-        Err(e) => {
-            eprintln!("Error loading wordlist: {}", e);
-            return;
-        }
-    };
+// Fuzzed URL and optional data for each word
+fn build_targets(base_url: &str, data: &Option<String>, words: &[String]) -> Vec<(String, Option<String>)> {
+    generate_urls(base_url, words)
+        .into_iter()
+        .zip(words)
+        .map(|(fuzzed_url, word)| (fuzzed_url, data.as_ref().map(|d| d.replace("FUZZ", word))))
+        .collect()
+}
 
-    // Parse raw headers into key-value pairs
+// Parses raw "Key: Value" header strings into key-value pairs
+pub fn parse_headers(raw_headers: Vec<String>) -> Vec<(String, String)> {
     let mut parsed_headers: Vec<(String, String)> = vec![];
     for h in raw_headers {
         let parts: Vec<&str> = h.splitn(2, ':').collect();
@@ -64,32 +74,11 @@ pub async fn fuzz(
             eprintln!("Invalid header format '{}'. Skipping. Use 'Key: Value'.", h);
         }
     }
+    parsed_headers
+}
 
-    // Compile regex filters
-    let mut compiled_regexes: Vec<Regex> = vec![];
-    for pattern in filter_regex {
-        match Regex::new(&pattern) {
-            Ok(re) => compiled_regexes.push(re),
-            Err(e) => eprintln!("Invalid regex pattern '{}': {}. Skipping.", pattern, e),
-        }
-    }
-
-    // Validate and uppercase method
-    let mut method_upper = method.to_uppercase();
-    if !["GET", "POST", "HEAD", "PUT"].contains(&method_upper.as_str()) {
-        eprintln!("Unsupported method: {}. Falling back to GET.", method);
-        method_upper = "GET".to_string();
-    }
-
-    // Generate targets (fuzzed URL and optional data)
-    let mut targets = vec![];
-    for word in &words {
-        let fuzzed_url = base_url.replace("FUZZ", word);
-        let fuzzed_data = data.as_ref().map(|d| d.replace("FUZZ", word));
-        targets.push((fuzzed_url, fuzzed_data));
-    }
-
-    // Build reqwest client with proxy if provided
+// Builds the shared reqwest client, routing through a proxy if provided
+pub fn build_client(proxy_url: Option<String>, proxy_auth: Option<String>) -> Client {
     let mut client_builder = Client::builder()
         .user_agent("Grimnir/0.1")
         .timeout(std::time::Duration::from_secs(5));
@@ -119,52 +108,469 @@ pub async fn fuzz(
         }
     }
 
-    let client = client_builder.build().expect("Failed to build reqwest client");
+    client_builder.build().expect("Failed to build reqwest client")
+}
+
+// Everything a scan is configured with, from the command line
+pub struct FuzzOptions {
+    pub base_url: String,                  // URL with FUZZ
+    pub wordlist_path: String,
+    pub concurrency: usize,                // Requests spawned per batch
+    pub filter_status: Option<Vec<u16>>,   // Statuses to drop
+    pub filter_size: Option<Vec<usize>>,   // Drop bodies shorter than any of these
+    pub rate: usize,                       // Requests per second
+    pub output: String,                    // Format, optionally ":file" (e.g. json:out.json)
+    pub method: String,
+    pub data: Option<String>,              // Request body with FUZZ
+    pub raw_headers: Vec<String>,          // "Name: value" strings from -H
+    pub tech_enabled: bool,
+    pub proxy_url: Option<String>,
+    pub proxy_auth: Option<String>,
+    pub filter_regex: Vec<String>,
+    pub filter_label: Vec<PageLabel>,      // Classifier labels to drop
+    pub match_anomaly: Option<f32>,        // Keep only results at least this anomalous
+    pub max_body: usize,
+    pub store_responses: Option<String>,   // Directory for full responses
+    pub favicon_enabled: bool,
+    pub signatures_dir: Option<String>,    // Custom fingerprint signatures
+    pub vuln_db_path: Option<PathBuf>,     // Set when CVE matching is on
+    pub fetch_js: bool,
+    pub active_probes: Option<String>,     // Some("") for bundled probes only
+    pub active_budget: usize,
+    pub waf_enabled: bool,
+    pub waf_rate: usize,                   // Rate once a WAF is seen (0 keeps rate)
+    pub cluster_enabled: bool,
+    pub collapse_clusters: bool,
+    pub learn_rounds: usize,               // 0 disables learning
+    pub learn_max: usize,
+    pub save_wordlist: Option<String>,     // File for harvested words
+    pub crawl_enabled: bool,
+    pub crawl_depth: usize,
+    pub crawl_pages: usize,
+    pub crawl_roots: usize,                // Crawled directories to fuzz under too
+    pub js_extract: bool,
+}
+
+// Main fuzz function: generates URLs, spawns async tasks for probing
+pub async fn fuzz(options: FuzzOptions, analyzers: Option<AnalyzerRegistry>) {
+    let FuzzOptions {
+        base_url,
+        wordlist_path,
+        concurrency,
+        filter_status,
+        filter_size,
+        rate,
+        output,
+        method,
+        data,
+        raw_headers,
+        tech_enabled,
+        proxy_url,
+        proxy_auth,
+        filter_regex,
+        filter_label,
+        match_anomaly,
+        max_body,
+        store_responses,
+        favicon_enabled,
+        signatures_dir,
+        vuln_db_path,
+        fetch_js,
+        active_probes,
+        active_budget,
+        waf_enabled,
+        waf_rate,
+        cluster_enabled,
+        collapse_clusters,
+        learn_rounds,
+        learn_max,
+        save_wordlist,
+        crawl_enabled,
+        crawl_depth,
+        crawl_pages,
+        crawl_roots,
+        js_extract,
+    } = options;
+    let words = match load_wordlist(&wordlist_path) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Error loading wordlist: {}", e);
+            return;
+        }
+    };
+
+    // Parse raw headers into key-value pairs
+    let parsed_headers = parse_headers(raw_headers);
+
+    // Compile regex filters
+    let mut compiled_regexes: Vec<Regex> = vec![];
+    for pattern in filter_regex {
+        match Regex::new(&pattern) {
+            Ok(re) => compiled_regexes.push(re),
+            Err(e) => eprintln!("Invalid regex pattern '{}': {}. Skipping.", pattern, e),
+        }
+    }
+
+    // Validate and uppercase method
+    let mut method_upper = method.to_uppercase();
+    if !["GET", "POST", "HEAD", "PUT"].contains(&method_upper.as_str()) {
+        eprintln!("Unsupported method: {}. Falling back to GET.", method);
+        method_upper = "GET".to_string();
+    }
+
+    // With crawling, the crawl's levels of GET requests come first, then the
+    // fuzz targets (built once the crawl is done)
+    let mut crawler: Option<Crawler> = None;
+    if crawl_enabled {
+        match Url::parse(base_url.split("FUZZ").next().unwrap_or("")) {
+            Ok(start) if start.has_host() => crawler = Some(Crawler::new(start, crawl_depth, crawl_pages)),
+            _ => eprintln!("Cannot crawl from '{}' (FUZZ must come after the host). Continuing without crawling.", base_url),
+        }
+    }
+    let mut crawling = crawler.is_some();
+
+    // Generate targets (fuzzed URL and optional data)
+    let mut targets = match crawler.as_mut() {
+        Some(crawler) => {
+            println!("Crawling (depth {}, up to {} pages)", crawl_depth, crawl_pages);
+            crawler.seeds().into_iter().map(|url| (url, None)).collect()
+        }
+        None => build_targets(&base_url, &data, &words),
+    };
+
+    // Words seen in responses, for extra rounds and/or a saved wordlist
+    // Harvested inside each task, while the response body is still there
+    let harvester = if learn_rounds > 0 || save_wordlist.is_some() {
+        Some(Arc::new(Mutex::new(WordHarvester::new(&words))))
+    } else {
+        None
+    };
+
+    // Build reqwest client with proxy if provided
+    let client = build_client(proxy_url, proxy_auth);
 
-    // Rate limiting semaphore
+    // Rate limiting semaphore; follow-up requests (favicons, scripts, active
+    // probes) take their own permit, so a lowered rate applies to them too
     let semaphore = Arc::new(Semaphore::new(rate));
 
+    // Per-scan directory for full responses (auto-named if no path given)
+    let store_dir: Option<Arc<PathBuf>> = match store_responses {
+        Some(dir) => {
+            let dir = if dir.is_empty() {
+                PathBuf::from(format!("grimnir_responses_{}", Local::now().format("%Y%m%d_%H%M%S")))
+            } else {
+                PathBuf::from(dir)
+            };
+            match std::fs::create_dir_all(&dir) {
+                Ok(_) => {
+                    println!("Storing responses in '{}'", dir.display());
+                    Some(Arc::new(dir))
+                }
+                Err(e) => {
+                    eprintln!("Failed to create response directory '{}': {}. Not storing.", dir.display(), e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Fingerprint rules are compiled once and shared by all tasks
+    let signatures = Arc::new(if tech_enabled { load_signatures(signatures_dir.as_deref()) } else { Default::default() });
+
+    // Offline CVE data, loaded once when --cve is set
+    let vuln_db: Option<Arc<VulnDb>> = match vuln_db_path {
+        Some(path) if tech_enabled => match VulnDb::load(&path) {
+            Ok(db) => {
+                println!("Loaded {} CVE(s) from '{}'", db.cve_count(), path.display());
+                Some(Arc::new(db))
+            }
+            Err(e) => {
+                eprintln!("{}. Run 'grimnir db import <feeds>' first. Continuing without CVE matching.", e);
+                None
+            }
+        },
+        Some(_) => {
+            eprintln!("CVE matching needs --tech. Continuing without it.");
+            None
+        }
+        None => None,
+    };
+
+    // Same-origin scripts are fetched once per scan, then fingerprinted and/or mined for endpoints
+    let script_cache: Option<ScriptCache> = if fetch_js && !tech_enabled && !js_extract {
+        eprintln!("Fetching JS needs --tech. Continuing without it.");
+        None
+    } else if fetch_js || js_extract {
+        Some(ScriptCache::new(parsed_headers.clone(), semaphore.clone()))
+    } else {
+        None
+    };
+
+    // Active probes run once per host; an empty file name means bundled probes only
+    let active: Option<ActiveFingerprinter> = match active_probes {
+        Some(file) if tech_enabled => {
+            let custom = if file.is_empty() { None } else { Some(file.as_str()) };
+            let headers = parsed_headers.clone();
+            Some(ActiveFingerprinter::new(load_probes(custom), signatures.clone(), active_budget, headers, semaphore.clone()))
+        }
+        Some(_) => {
+            eprintln!("Active fingerprinting needs --tech. Continuing without it.");
+            None
+        }
+        None => None,
+    };
+
+    // Favicons are fetched once per host and shared by all its results
+    let favicon_cache = FaviconCache::new(parsed_headers.clone(), semaphore.clone());
+
+    // Analyzers that compare against "what a miss looks like" get responses
+    // to random words first; then they are shared by all tasks
+    let mut analyzers = analyzers;
+    if let Some(registry) = analyzers.as_mut().filter(|a| a.needs_calibration()) {
+        let baselines = calibrate(&base_url, &data, &client, &method_upper, &parsed_headers, max_body).await;
+        println!("Calibrated against {} baseline response(s)", baselines.len());
+        registry.calibrate(&baselines);
+    }
+    let analyzers = analyzers.map(Arc::new);
+
+    // WAF/CDN detection: one pre-flight probe against the base URL, then a
+    // passive check of every response; the first detection lowers the rate
+    let wafs = Arc::new(if waf_enabled { WafSet::bundled() } else { WafSet::default() });
+    let throttled = Arc::new(AtomicBool::new(false));
+    if waf_enabled {
+        let preflight_url = base_url.replace("FUZZ", "");
+        match wafs.probe(&client, &preflight_url, &parsed_headers, max_body).await {
+            Ok(Some(waf)) => {
+                print_waf(&waf);
+                lower_rate(&semaphore, &throttled, rate, waf_rate);
+            }
+            Ok(None) => println!("No WAF/CDN detected on {}", preflight_url),
+            Err(e) => eprintln!("Could not probe {} for a WAF/CDN ({}); passive detection still runs", preflight_url, e),
+        }
+    }
+
     let mut handles = vec![];
-    let mut results: Vec<(ProbeResult, Option<(f32, String)>, Option<Vec<String>>)> = vec![];  // Collect with AI and tech
-
-    // Chunk and process targets
-    for chunk in targets.chunks(concurrency) {
-        for (url, opt_data) in chunk {
-            let url_clone = url.clone();
-            let opt_data_clone = opt_data.clone();
-            let client_clone = client.clone();
-            let sem_clone = semaphore.clone();
-            let method_clone = method_upper.clone();
-            let parsed_headers_clone = parsed_headers.clone();
-            let compiled_regexes_clone = compiled_regexes.clone();  // Clone for task
-            let handle = task::spawn(async move {
-                let _permit = sem_clone.acquire().await.unwrap();
-                match probe_url(url_clone.clone(), &client_clone, &method_clone, opt_data_clone, parsed_headers_clone).await {
-                    Ok(result) => {
-                        if should_filter(&result, &filter_status, &filter_size, &compiled_regexes_clone) {
-                            return None;
+    let mut results: Vec<ScanResult> = vec![];  // Collect with AI and tech
+
+    // Chunk and process targets; with learning, each round fuzzes words
+    // harvested from the previous one
+    let mut round = 0;
+    let mut js_round = false;
+    let mut js_rounds = 0;
+    let mut requested: HashSet<String> = HashSet::new();  // So JS endpoints are not requested twice
+    loop {
+        let round_start = results.len();
+        if js_extract {
+            requested.extend(targets.iter().filter(|(_, d)| d.is_none()).map(|(url, _)| url.clone()));
+        }
+        let mut found: Vec<Endpoint> = vec![];  // Referenced by this round's responses, when crawling
+        for chunk in targets.chunks(concurrency) {
+            for (url, opt_data) in chunk {
+                let url_clone = url.clone();
+                let opt_data_clone = opt_data.clone();
+                let client_clone = client.clone();
+                let sem_clone = semaphore.clone();
+                let method_clone = if crawling || js_round { "GET".to_string() } else { method_upper.clone() };
+                let crawl_scope_clone = if crawling { crawler.as_ref().map(|c| c.scope()) } else { None };
+                let parsed_headers_clone = parsed_headers.clone();
+                let compiled_regexes_clone = compiled_regexes.clone();  // Clone for task
+                let filter_status_clone = filter_status.clone();
+                let filter_size_clone = filter_size.clone();
+                let filter_label_clone = filter_label.clone();
+                let store_dir_clone = store_dir.clone();
+                let favicon_cache_clone = favicon_cache.clone();
+                let signatures_clone = signatures.clone();
+                let vuln_db_clone = vuln_db.clone();
+                let active_clone = active.clone();
+                let analyzers_clone = analyzers.clone();
+                let script_cache_clone = script_cache.clone();
+                let harvester_clone = harvester.clone();
+                let wafs_clone = wafs.clone();
+                let throttled_clone = throttled.clone();
+                let handle = task::spawn(async move {
+                    // The permit covers this request only; holding it while follow-up
+                    // requests wait for theirs could use up every permit
+                    let permit = sem_clone.acquire().await.unwrap();
+                    let response = probe_url(url_clone.clone(), &client_clone, &method_clone, opt_data_clone, parsed_headers_clone, max_body).await;
+                    drop(permit);
+                    match response {
+                        Ok(mut result) => {
+                            // Crawl links come from every response, kept or filtered
+                            let found = crawl_scope_clone.as_ref().map_or(vec![], |scope| scope.extract(&result));
+                            // Block pages are often filtered (--filter-status 403), so WAFs are looked for first
+                            if waf_enabled {
+                                result.waf = wafs_clone.detect(&result);
+                                if let Some(waf) = &result.waf {
+                                    if !throttled_clone.load(Ordering::SeqCst) {
+                                        print_waf(waf);
+                                    }
+                                    lower_rate(&sem_clone, &throttled_clone, rate, waf_rate);
+                                }
+                            }
+                            if should_filter(&result, &filter_status_clone, &filter_size_clone, &compiled_regexes_clone) {
+                                return (None, found);
+                            }
+                            if favicon_enabled {
+                                result.favicon = favicon_cache_clone.get(&client_clone, &result.url, result.html.as_ref()).await;
+                            }
+                            if let Some(dir) = &store_dir_clone {
+                                match store_response(dir, &result) {
+                                    Ok(path) => result.stored_path = Some(path.display().to_string()),
+                                    Err(e) => eprintln!("Failed to store response for {}: {}", url_clone, e),
+                                }
+                            }
+                            if let Some(cache) = &script_cache_clone {
+                                result.scripts = cache.fetch_for(&client_clone, &result.url, result.html.as_ref()).await;
+                            }
+                            if js_extract {
+                                result.js = js_extract::scan(&result);
+                            }
+                            // Tech first, so analyzers can take detected technologies into account
+                            let tech_opt = if tech_enabled {
+                                let active_detections = match &active_clone {
+                                    Some(a) => a.get(&client_clone, &result.url).await,
+                                    None => vec![],
+                                };
+                                let mut detections = fingerprint(&result, &signatures_clone, &active_detections);
+                                if let Some(db) = &vuln_db_clone {
+                                    db.annotate(&mut detections);
+                                }
+                                result.tech = labels(&detections);
+                                Some(detections)
+                            } else {
+                                None
+                            };
+                            let ai_opt = match &analyzers_clone {
+                                Some(a) => Some(a.analyze(&result).await),
+                                None => None,
+                            };
+                            let label = ai_opt.as_ref().and_then(|a| a.label.as_ref()).map(|l| l.label);
+                            if label.is_some_and(|l| filter_label_clone.contains(&l)) {
+                                return (None, found);
+                            }
+                            if let Some(harvester) = harvester_clone.filter(|_| worth_learning(&result, ai_opt.as_ref())) {
+                                harvester.lock().unwrap().harvest(&result);
+                            }
+                            if cluster_enabled {
+                                result.signature = Some(clustering::signature(&result));
+                            }
+                            // Kept results hold metadata only; memory no longer grows with --max-body
+                            result.release_body();
+                            (Some((result, ai_opt, tech_opt)), found)
+                        }
+                        Err(e) => {
+                            eprintln!("Probe error for {}: {}", url_clone, e);
+                            (None, vec![])
                         }
-                        let ai_opt = if ai_enabled { Some(analyze(&result)) } else { None };
-                        let tech_opt = if tech_enabled { Some(fingerprint(&result)) } else { None };
-                        Some((result, ai_opt, tech_opt))
                     }
-                    Err(e) => {
-                        eprintln!("Probe error for {}: {}", url_clone, e);
-                        None
+                });
+                handles.push(handle);
+            }
+
+            // Wait for batch and collect
+            for handle in handles.drain(..) {
+                match handle.await {
+                    Ok((kept, endpoints)) => {
+                        results.extend(kept);
+                        found.extend(endpoints);
                     }
+                    Err(e) => eprintln!("Task error: {}", e),
                 }
-            });
-            handles.push(handle);
+            }
         }
 
-        // Wait for batch and collect
-        for handle in handles.drain(..) {
-            if let Ok(Some(res)) = handle.await {
-                results.push(res);
-            } else if let Err(e) = handle.await {
-                eprintln!("Task error: {}", e);
+        if let Some(crawler) = crawler.as_mut().filter(|_| crawling) {
+            let next = crawler.next_level(found);
+            if !next.is_empty() {
+                println!("Crawling {} new page(s) at depth {}", next.len(), crawler.depth());
+                targets = next.into_iter().map(|url| (url, None)).collect();
+                continue;
+            }
+            crawling = false;
+            print_crawl_summary(crawler);
+            // Then the fuzz proper, also under the directories the crawl found
+            let mut fuzz = build_targets(&base_url, &data, &words);
+            if crawl_roots > 0 && base_url.ends_with("/FUZZ") {
+                let roots: Vec<String> = crawler.directories().into_iter().take(crawl_roots).collect();
+                if !roots.is_empty() {
+                    println!("Fuzzing under {} crawled director(ies) too: {}", roots.len(), roots.join(", "));
+                }
+                for root in roots {
+                    fuzz.extend(build_targets(&format!("{}FUZZ", root), &data, &words));
+                }
+            }
+            fuzz.retain(|(url, d)| d.is_some() || !crawler.was_requested(url));
+            targets = fuzz;
+            continue;
+        }
+
+        // Same-origin endpoints the round's JavaScript referenced, as GET requests
+        if js_extract && js_rounds < JS_ROUNDS {
+            let mut new: Vec<String> = vec![];
+            for (result, _, _) in &results[round_start..] {
+                for url in result.js.as_ref().map_or(vec![], |js| js.targets(&result.url)) {
+                    if !requested.contains(&url) && !new.contains(&url) {
+                        new.push(url);
+                    }
+                }
+            }
+            if !new.is_empty() {
+                js_round = true;
+                js_rounds += 1;
+                println!("Requesting {} endpoint(s) found in JavaScript", new.len());
+                targets = new.into_iter().map(|url| (url, None)).collect();
+                continue;
+            }
+        }
+        js_round = false;
+        js_rounds = 0;
+
+        if let Some(harvester) = &harvester {
+            if round < learn_rounds {
+                round += 1;
+                let learned = harvester.lock().unwrap().next_words(learn_max);
+                if !learned.is_empty() {
+                    println!("Learning round {}/{}: fuzzing {} word(s) found in responses", round, learn_rounds, learned.len());
+                    targets = build_targets(&base_url, &data, &learned);
+                    continue;
+                }
             }
         }
+        break;
+    }
+
+    // Analyzers that judge results against the whole scan (anomaly) get a last pass
+    if let Some(registry) = &analyzers {
+        let mut analyzed: Vec<(&ProbeResult, &mut Analysis)> =
+            results.iter_mut().filter_map(|(r, a, _)| a.as_mut().map(|a| (&*r, a))).collect();
+        registry.finish(&mut analyzed);
+    }
+    if let Some(min) = match_anomaly {
+        results.retain(|(_, a, _)| a.as_ref().and_then(|a| a.anomaly.as_ref()).is_some_and(|x| x.score >= min));
+    }
+
+    if let (Some(harvester), Some(path)) = (&harvester, &save_wordlist) {
+        match harvester.lock().unwrap().save(path) {
+            Ok(count) => println!("Saved {} harvested word(s) to '{}'", count, path),
+            Err(e) => eprintln!("Failed to write wordlist '{}': {}", path, e),
+        }
+    }
+
+    // Group similar responses once everything is in, in scan order
+    let scanned = results.len();
+    if cluster_enabled {
+        let signatures: Vec<PageSignature> = results
+            .iter_mut()
+            .map(|(r, _, _)| r.signature.take().unwrap_or_else(|| clustering::signature(r)))
+            .collect();
+        let clusters = clustering::cluster(&signatures);
+        for ((result, _, _), info) in results.iter_mut().zip(clusters) {
+            result.cluster = Some(info);
+        }
+        if collapse_clusters {
+            results.retain(|(r, _, _)| r.cluster.as_ref().is_some_and(|c| c.representative));
+        }
     }
 
     // Parse output arg (e.g., "json:output.json")
@@ -218,11 +624,42 @@ pub async fn fuzz(
                 if let Some(server) = result.headers.get("server") {
                     text.push_str(&format!("Server: {}\n", server));
                 }
-                if let Some((score, insights)) = ai_opt {
-                    text.push_str(&format!("AI Score: {:.2}\nInsights: {}\n", score, insights));
+                if let Some(favicon) = &result.favicon {
+                    text.push_str(&format!("Favicon: mmh3 {} md5 {}\n", favicon.mmh3, favicon.md5));
+                }
+                if let Some(waf) = &result.waf {
+                    text.push_str(&format!("WAF: {} ({}%)\n", waf.vendor, waf.confidence));
+                }
+                if let Some(cluster) = &result.cluster {
+                    text.push_str(&format!("{}\n", cluster_line(cluster)));
+                }
+                for line in result.js.as_ref().map_or(vec![], js_lines) {
+                    text.push_str(&format!("{}\n", line));
+                }
+                if let Some(analysis) = ai_opt {
+                    text.push_str(&format!("AI Score: {:.2}\n", analysis.score));
+                    if let Some(line) = breakdown_line(&analysis.components) {
+                        text.push_str(&format!("{}\n", line));
+                    }
+                    text.push_str(&format!("Insights: {}\n", analysis.insights.join("; ")));
+                    if let Some(label) = &analysis.label {
+                        text.push_str(&format!("Label: {} ({}%)\n", label.label.as_str(), label.confidence));
+                    }
+                    if let Some(anomaly) = &analysis.anomaly {
+                        text.push_str(&format!("{}\n", anomaly_line(anomaly)));
+                    }
+                    for line in secret_lines(&analysis.secrets) {
+                        text.push_str(&format!("{}\n", line));
+                    }
+                    if let Some(triage) = &analysis.triage {
+                        text.push_str(&format!("LLM Triage: {} / {}: {}\n", triage.severity, triage.category, triage.explanation));
+                    }
                 }
                 if let Some(tech) = tech_opt {
-                    text.push_str(&format!("Detected Tech: {}\n", tech.join(", ")));
+                    text.push_str(&format!("Detected Tech: {}\n", labels(tech).join(", ")));
+                    for line in vulnerability_lines(tech) {
+                        text.push_str(&format!("{}\n", line));
+                    }
                 }
                 text.push_str("---\n");
             }
@@ -237,9 +674,104 @@ pub async fn fuzz(
         println!("{}", c);
     }
 
+    if cluster_enabled {
+        print_cluster_summary(&results, scanned);
+    }
     println!("Fuzzing complete!");
 }
 
+// Every endpoint the crawl found, with its parameters
+fn print_crawl_summary(crawler: &Crawler) {
+    println!("Crawled {} page(s), found {} endpoint(s):", crawler.pages(), crawler.endpoints().len());
+    for endpoint in crawler.endpoints() {
+        if endpoint.params.is_empty() {
+            println!("  {} {}", endpoint.method, endpoint.url);
+        } else {
+            println!("  {} {} [{}]", endpoint.method, endpoint.url, endpoint.params.join(", "));
+        }
+    }
+}
+
+// Misses, error pages and WAF blocks echo the request rather than describe
+// the target, so their words are not learned
+fn worth_learning(result: &ProbeResult, analysis: Option<&Analysis>) -> bool {
+    let label = analysis.and_then(|a| a.label.as_ref()).map(|l| l.label);
+    !matches!(result.status, 404 | 410)
+        && label.is_none_or(|l| matches!(l, PageLabel::RealContent | PageLabel::Login))
+}
+
+// Requests two words that should not exist (one with an extension, as
+// servers often route those differently) and returns (word, response) pairs
+async fn calibrate(
+    base_url: &str,
+    data: &Option<String>,
+    client: &Client,
+    method: &str,
+    headers: &[(String, String)],
+    max_body: usize,
+) -> Vec<(String, ProbeResult)> {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(Local::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+    let token = format!("grimnir{:012x}", hasher.finish() & 0xffff_ffff_ffff);
+    let mut baselines = vec![];
+    for word in [token.clone(), format!("{}.php", token)] {
+        let url = base_url.replace("FUZZ", &word);
+        let body = data.as_ref().map(|d| d.replace("FUZZ", &word));
+        match probe_url(url.clone(), client, method, body, headers.to_vec(), max_body).await {
+            Ok(result) => baselines.push((word, result)),
+            Err(e) => eprintln!("Calibration request to {} failed: {}", url, e),
+        }
+    }
+    baselines
+}
+
+// Per-result cluster line, e.g. "Cluster: 3 (42 similar, representative)"
+fn cluster_line(cluster: &ClusterInfo) -> String {
+    let role = if cluster.representative { ", representative" } else { "" };
+    format!("Cluster: {} ({} similar{})", cluster.id, cluster.size, role)
+}
+
+// One line per cluster, largest first, naming the member to triage
+fn print_cluster_summary(results: &[ScanResult], scanned: usize) {
+    let mut reps: Vec<(&ClusterInfo, &ProbeResult)> = results
+        .iter()
+        .filter_map(|(r, _, _)| r.cluster.as_ref().filter(|c| c.representative).map(|c| (c, r)))
+        .collect();
+    reps.sort_by(|a, b| b.0.size.cmp(&a.0.size).then(a.0.id.cmp(&b.0.id)));
+    println!("{} result(s) in {} cluster(s):", scanned, reps.len());
+    for (cluster, result) in reps {
+        let title = result.title.as_deref().map(|t| format!(" \"{}\"", t)).unwrap_or_default();
+        println!("  #{} x{}: [{}] {}{}", cluster.id, cluster.size, result.status, result.url, title);
+    }
+}
+
+// Announces a detected WAF/CDN with its evidence and hints
+fn print_waf(waf: &WafDetection) {
+    println!("WAF/CDN detected: {} ({}, {}%)", waf.vendor, waf.kind, waf.confidence);
+    for line in &waf.evidence {
+        println!("  evidence: {}", line);
+    }
+    for hint in &waf.hints {
+        println!("  hint: {}", hint);
+    }
+}
+
+// Drops the semaphore from `rate` to `waf_rate` permits, once per scan.
+// Permits still held by running tasks are withdrawn as they are released.
+fn lower_rate(semaphore: &Arc<Semaphore>, throttled: &AtomicBool, rate: usize, waf_rate: usize) {
+    if waf_rate == 0 || waf_rate >= rate || throttled.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let excess = (rate - waf_rate) as u32;
+    println!("Lowering rate from {} to {} because of the WAF", rate, waf_rate);
+    let semaphore = semaphore.clone();
+    task::spawn(async move {
+        if let Ok(permits) = semaphore.acquire_many(excess).await {
+            permits.forget();
+        }
+    });
+}
+
 // Helper to check if result should be filtered
 fn should_filter(
     result: &ProbeResult,
@@ -254,15 +786,15 @@ fn should_filter(
         }
     }
 
-    // Size filter (using title length as placeholder; update if adding body_len)
+    // Size filter (bytes read, so capped at max_body)
     if let Some(sizes) = filter_size {
-        let body_size = result.title.as_ref().map_or(0, |t| t.len());
-        if sizes.iter().any(|&s| body_size < s) {
+        if sizes.iter().any(|&s| result.body_len < s) {
             return true;
         }
     }
 
     // Regex filters (filter out if any matches)
+    let body = result.body_text();
     for re in filter_regexes {
         if re.is_match(&result.url) {
             return true;
@@ -272,10 +804,8 @@ fn should_filter(
                 return true;
             }
         }
-        if let Some(body) = &result.body_snippet {
-            if re.is_match(body) {
-                return true;
-            }
+        if re.is_match(&body) {
+            return true;
         }
         if let Some(server) = result.headers.get("server") {
             if re.is_match(server) {
@@ -287,8 +817,79 @@ fn should_filter(
     false
 }
 
+// One summary line per vulnerable detection (top CVEs by CVSS)
+fn vulnerability_lines(detections: &[Detection]) -> Vec<String> {
+    detections
+        .iter()
+        .filter(|d| !d.vulnerabilities.is_empty())
+        .map(|d| {
+            let top: Vec<String> = d
+                .vulnerabilities
+                .iter()
+                .take(5)
+                .map(|v| match v.cvss {
+                    Some(score) => format!("{} ({:.1})", v.id, score),
+                    None => v.id.clone(),
+                })
+                .collect();
+            let more = d.vulnerabilities.len().saturating_sub(top.len());
+            let suffix = if more > 0 { format!(" +{} more", more) } else { String::new() };
+            format!("Vulnerabilities ({} {}): {}{}", d.name, d.version.as_deref().unwrap_or(""), top.join(", "), suffix)
+        })
+        .collect()
+}
+
+// One line per secret finding, e.g. "Secret [high] AWS access key ID (body 12:5): AKIA********MPLE"
+fn secret_lines(findings: &[SecretFinding]) -> Vec<String> {
+    findings
+        .iter()
+        .map(|f| format!("Secret [{}] {} ({} {}:{}): {}", f.severity.as_str(), f.kind, f.source, f.line, f.column, f.redacted))
+        .collect()
+}
+
+// Which rules moved the score, e.g. "Score breakdown: heuristics/success +0.50, sentiment/negative_title -0.20"
+// (raw values, before analyzer weights)
+fn breakdown_line(components: &[ScoreComponent]) -> Option<String> {
+    if components.is_empty() {
+        return None;
+    }
+    let parts: Vec<String> = components.iter().map(|c| format!("{}/{} {:+.2}", c.analyzer, c.name, c.value)).collect();
+    Some(format!("Score breakdown: {}", parts.join(", ")))
+}
+
+// What a result's JavaScript references: endpoints on one line (with the call
+// they were passed to), then a line per GraphQL operation and per key, e.g.
+// "JS endpoints: /api/users (fetch), https://cdn.example.com/sdk.js"
+// "JS key apiKey (https://example.com/app.js 12): sk_l********9f2a"
+fn js_lines(js: &JsFindings) -> Vec<String> {
+    let mut lines = vec![];
+    if !js.endpoints.is_empty() {
+        let endpoints: Vec<String> = js
+            .endpoints
+            .iter()
+            .map(|e| match &e.context {
+                Some(context) => format!("{} ({})", e.value, context),
+                None => e.value.clone(),
+            })
+            .collect();
+        lines.push(format!("JS endpoints: {}", endpoints.join(", ")));
+    }
+    for op in &js.graphql {
+        lines.push(format!("GraphQL {} {} ({} {})", op.kind, op.name.as_deref().unwrap_or("(anonymous)"), op.source, op.line));
+    }
+    for key in &js.keys {
+        lines.push(format!("JS key {} ({} {}): {}", key.name, key.source, key.line, key.redacted));
+    }
+    lines
+}
+
+// e.g. "Anomaly: 0.75 (rare status 500 (0 of 240), length 5123 vs ~312 (z 41.2))"
+fn anomaly_line(anomaly: &Anomaly) -> String {
+    format!("Anomaly: {:.2} ({})", anomaly.score, anomaly.reasons.join(", "))
+}
+
 // Terminal output (pretty print)
-fn output_terminal(results: &[(ProbeResult, Option<(f32, String)>, Option<Vec<String>>)]) {
+fn output_terminal(results: &[ScanResult]) {
     for (result, ai_opt, tech_opt) in results {
         println!("URL: {}", result.url);
         println!("Status: {}", result.status);
@@ -298,12 +899,46 @@ fn output_terminal(results: &[(ProbeResult, Option<(f32, String)>, Option<Vec<St
         if let Some(server) = result.headers.get("server") {
             println!("Server: {}", server);
         }
-        if let Some((score, insights)) = ai_opt {
-            println!("AI Score: {:.2}", score);
-            println!("Insights: {}", insights);
+        if !result.cookies.is_empty() {
+            let names: Vec<&str> = result.cookies.iter().map(|c| c.name.as_str()).collect();
+            println!("Cookies: {}", names.join(", "));
+        }
+        if let Some(favicon) = &result.favicon {
+            println!("Favicon: mmh3 {} md5 {}", favicon.mmh3, favicon.md5);
+        }
+        if let Some(waf) = &result.waf {
+            println!("WAF: {} ({}%)", waf.vendor, waf.confidence);
+        }
+        if let Some(cluster) = &result.cluster {
+            println!("{}", cluster_line(cluster));
+        }
+        for line in result.js.as_ref().map_or(vec![], js_lines) {
+            println!("{}", line);
+        }
+        if let Some(analysis) = ai_opt {
+            println!("AI Score: {:.2}", analysis.score);
+            if let Some(line) = breakdown_line(&analysis.components) {
+                println!("{}", line);
+            }
+            println!("Insights: {}", analysis.insights.join("; "));
+            if let Some(label) = &analysis.label {
+                println!("Label: {} ({}%)", label.label.as_str(), label.confidence);
+            }
+            if let Some(anomaly) = &analysis.anomaly {
+                println!("{}", anomaly_line(anomaly));
+            }
+            for line in secret_lines(&analysis.secrets) {
+                println!("{}", line);
+            }
+            if let Some(triage) = &analysis.triage {
+                println!("LLM Triage: {} / {}: {}", triage.severity, triage.category, triage.explanation);
+            }
         }
         if let Some(tech) = tech_opt {
-            println!("Detected Tech: {}", tech.join(", "));
+            println!("Detected Tech: {}", labels(tech).join(", "));
+            for line in vulnerability_lines(tech) {
+                println!("{}", line);
+            }
         }
         println!("---");
     }
@@ -314,22 +949,54 @@ fn output_terminal(results: &[(ProbeResult, Option<(f32, String)>, Option<Vec<St
 struct JsonResult {
     url: String,
     status: u16,
-    headers: std::collections::HashMap<String, String>,
+    headers: Headers,
+    cookies: Vec<Cookie>,
     title: Option<String>,
+    html: Option<HtmlInfo>,
+    body_len: usize,
+    body_truncated: bool,
+    body_sha256: Option<String>,
+    elapsed_ms: u64,
+    stored_path: Option<String>,
+    favicon: Option<FaviconHash>,
+    waf: Option<WafDetection>,
+    cluster: Option<ClusterInfo>,
+    js: Option<JsFindings>,
     ai_score: Option<f32>,
     ai_insights: Option<String>,
-    detected_tech: Option<Vec<String>>,
+    ai_components: Option<Vec<ScoreComponent>>,
+    ai_label: Option<Classification>,
+    ai_secrets: Option<Vec<SecretFinding>>,
+    ai_triage: Option<LlmVerdict>,
+    ai_anomaly: Option<Anomaly>,
+    detected_tech: Option<Vec<Detection>>,
 }
 
-fn build_json_results(results: &[(ProbeResult, Option<(f32, String)>, Option<Vec<String>>)]) -> Vec<JsonResult> {
+fn build_json_results(results: &[ScanResult]) -> Vec<JsonResult> {
     results.iter().map(|(res, ai_opt, tech_opt)| {
         JsonResult {
             url: res.url.clone(),
             status: res.status,
             headers: res.headers.clone(),
+            cookies: res.cookies.clone(),
             title: res.title.clone(),
-            ai_score: ai_opt.map(|(score, _)| score),
-            ai_insights: ai_opt.map(|(_, insights)| insights.clone()),
+            html: res.html.clone(),
+            body_len: res.body_len,
+            body_truncated: res.body_truncated,
+            body_sha256: res.body_sha256.clone(),
+            elapsed_ms: res.elapsed_ms,
+            stored_path: res.stored_path.clone(),
+            favicon: res.favicon.clone(),
+            waf: res.waf.clone(),
+            cluster: res.cluster.clone(),
+            js: res.js.clone(),
+            ai_score: ai_opt.as_ref().map(|a| a.score),
+            ai_insights: ai_opt.as_ref().map(|a| a.insights.join("; ")),
+            ai_components: ai_opt.as_ref().map(|a| a.components.clone()),
+            ai_label: ai_opt.as_ref().and_then(|a| a.label.clone()),
+            ai_secrets: ai_opt.as_ref().filter(|a| !a.secrets.is_empty()).map(|a| a.secrets.clone()),
+            ai_triage: ai_opt.as_ref().and_then(|a| a.triage.clone()),
+            ai_anomaly: ai_opt.as_ref().and_then(|a| a.anomaly.clone()),
             detected_tech: tech_opt.clone(),
         }
     }).collect()
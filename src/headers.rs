@@ -0,0 +1,120 @@
+use serde::Serialize;
+
+// Ordered response headers that keep every value of repeated headers
+// (e.g. several Set-Cookie lines). Names are lowercase: reqwest does not
+// expose the case the server sent (and HTTP/2 names are always lowercase).
+// Serialized as a list of [name, value] pairs, not an object.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct Headers(Vec<(String, String)>);
+
+impl Headers {
+    pub fn push(&mut self, name: String, value: String) {
+        self.0.push((name, value));
+    }
+
+    // First value of a header (case-insensitive name)
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.0.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+    }
+
+    // Every value of a header, in response order
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.0.iter().filter(move |(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.0.iter().map(|(k, v)| (k, v))
+    }
+}
+
+// A cookie parsed from one Set-Cookie header
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub expires: Option<String>,
+    pub max_age: Option<String>,
+    pub same_site: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+// Parses "name=value; Attr=...; Flag" per RFC 6265 (lenient: unknown attributes ignored)
+pub fn parse_set_cookie(header: &str) -> Option<Cookie> {
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().trim_matches('"').to_string(),
+        ..Default::default()
+    };
+    for attr in parts {
+        let (key, val) = match attr.split_once('=') {
+            Some((k, v)) => (k.trim(), Some(v.trim().to_string())),
+            None => (attr.trim(), None),
+        };
+        match key.to_lowercase().as_str() {
+            "domain" => cookie.domain = val,
+            "path" => cookie.path = val,
+            "expires" => cookie.expires = val,
+            "max-age" => cookie.max_age = val,
+            "samesite" => cookie.same_site = val,
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+    Some(cookie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_headers_are_all_kept_in_order() {
+        let mut headers = Headers::default();
+        headers.push("set-cookie".to_string(), "a=1".to_string());
+        headers.push("server".to_string(), "nginx".to_string());
+        headers.push("set-cookie".to_string(), "b=2".to_string());
+        assert_eq!(headers.get("Set-Cookie").map(String::as_str), Some("a=1"));
+        assert_eq!(headers.get_all("SET-COOKIE").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert!(headers.get("x-missing").is_none());
+        assert_eq!(
+            serde_json::to_string(&headers).unwrap(),
+            r#"[["set-cookie","a=1"],["server","nginx"],["set-cookie","b=2"]]"#
+        );
+    }
+
+    #[test]
+    fn set_cookie_attributes_and_flags() {
+        let cookie = parse_set_cookie(
+            r#"laravel_session="abc=def"; Path=/; Domain=.example.com; Max-Age=7200; expires=Wed, 21 Oct 2026 07:28:00 GMT; SameSite=Lax; secure; HttpOnly; Priority=High"#,
+        )
+        .unwrap();
+        assert_eq!(cookie.name, "laravel_session");
+        assert_eq!(cookie.value, "abc=def");
+        assert_eq!(cookie.path.as_deref(), Some("/"));
+        assert_eq!(cookie.domain.as_deref(), Some(".example.com"));
+        assert_eq!(cookie.max_age.as_deref(), Some("7200"));
+        assert_eq!(cookie.expires.as_deref(), Some("Wed, 21 Oct 2026 07:28:00 GMT"));
+        assert_eq!(cookie.same_site.as_deref(), Some("Lax"));
+        assert!(cookie.secure && cookie.http_only);
+    }
+
+    #[test]
+    fn malformed_cookies_are_rejected() {
+        assert!(parse_set_cookie("no-equals-sign").is_none());
+        assert!(parse_set_cookie(" =value").is_none());
+        let empty = parse_set_cookie("csrftoken=").unwrap();
+        assert_eq!(empty.name, "csrftoken");
+        assert!(empty.value.is_empty() && !empty.secure && !empty.http_only);
+    }
+}
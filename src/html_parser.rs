@@ -0,0 +1,204 @@
+use lazy_static::lazy_static;
+use scraper::{ElementRef, Html, Node, Selector};
+use serde::Serialize;
+use std::collections::HashSet;
+
+// Structured view of an HTML document, shared by fingerprinting, AI and crawling
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HtmlInfo {
+    pub title: Option<String>,
+    pub meta: Vec<MetaTag>,
+    pub forms: Vec<HtmlForm>,
+    pub links: Vec<String>,    // Raw href/src values from a, area, iframe, frame
+    pub scripts: Vec<String>,  // Raw script src values
+    pub icons: Vec<String>,    // Raw <link rel="icon"> hrefs
+    pub comments: Vec<String>,
+    #[serde(skip)]
+    pub inline_scripts: Vec<String>,  // Bodies of <script> elements without src
+}
+
+// A <meta> tag keyed by name, property or http-equiv (lowercased)
+#[derive(Debug, Clone, Serialize)]
+pub struct MetaTag {
+    pub name: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HtmlForm {
+    pub action: Option<String>,
+    pub method: String,  // Uppercased, GET if absent
+    pub inputs: Vec<FormInput>,
+}
+
+// An input, select or textarea inside a form
+#[derive(Debug, Clone, Serialize)]
+pub struct FormInput {
+    pub name: Option<String>,
+    pub input_type: String,
+    pub value: Option<String>,
+}
+
+impl HtmlInfo {
+    // True if any form asks for a password (login/registration pages)
+    pub fn has_password_form(&self) -> bool {
+        self.forms.iter().any(|f| f.inputs.iter().any(|i| i.input_type == "password"))
+    }
+}
+
+lazy_static! {
+    static ref TITLE_SEL: Selector = Selector::parse("title").unwrap();
+    static ref META_SEL: Selector = Selector::parse("meta").unwrap();
+    static ref FORM_SEL: Selector = Selector::parse("form").unwrap();
+    static ref INPUT_SEL: Selector = Selector::parse("input, select, textarea").unwrap();
+    static ref LINK_SEL: Selector = Selector::parse("a[href], area[href], iframe[src], frame[src]").unwrap();
+    static ref SCRIPT_SEL: Selector = Selector::parse("script[src]").unwrap();
+    static ref INLINE_SCRIPT_SEL: Selector = Selector::parse("script:not([src])").unwrap();
+    static ref ICON_SEL: Selector = Selector::parse(r#"link[rel~="icon"][href]"#).unwrap();
+}
+
+// Collapses runs of whitespace so multi-line titles read as one line
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// Returns a trimmed, non-empty attribute value
+fn attr(el: &ElementRef, name: &str) -> Option<String> {
+    el.value().attr(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+// Parses a document with a tolerant HTML5 parser (case, attributes and entities handled)
+pub fn parse_html(text: &str) -> HtmlInfo {
+    let doc = Html::parse_document(text);
+
+    let title = doc
+        .select(&TITLE_SEL)
+        .next()
+        .map(|t| normalize_text(&t.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+
+    let meta = doc
+        .select(&META_SEL)
+        .filter_map(|m| {
+            let key = attr(&m, "name").or_else(|| attr(&m, "property")).or_else(|| attr(&m, "http-equiv"))?;
+            Some(MetaTag { name: key.to_lowercase(), content: attr(&m, "content").unwrap_or_default() })
+        })
+        .collect();
+
+    let forms = doc
+        .select(&FORM_SEL)
+        .map(|f| HtmlForm {
+            action: attr(&f, "action"),
+            method: attr(&f, "method").map_or("GET".to_string(), |m| m.to_uppercase()),
+            inputs: f
+                .select(&INPUT_SEL)
+                .map(|i| FormInput {
+                    name: attr(&i, "name"),
+                    input_type: match i.value().name() {
+                        "input" => attr(&i, "type").map_or("text".to_string(), |t| t.to_lowercase()),
+                        other => other.to_string(),
+                    },
+                    value: attr(&i, "value"),
+                })
+                .collect(),
+        })
+        .collect();
+
+    // Document order, without repeats (the set keeps this linear on link-heavy pages)
+    let mut links: Vec<String> = vec![];
+    let mut seen_links = HashSet::new();
+    for el in doc.select(&LINK_SEL) {
+        if let Some(target) = attr(&el, "href").or_else(|| attr(&el, "src")) {
+            if seen_links.insert(target.clone()) {
+                links.push(target);
+            }
+        }
+    }
+
+    let mut scripts: Vec<String> = vec![];
+    let mut seen_scripts = HashSet::new();
+    for el in doc.select(&SCRIPT_SEL) {
+        if let Some(src) = attr(&el, "src") {
+            if seen_scripts.insert(src.clone()) {
+                scripts.push(src);
+            }
+        }
+    }
+
+    let inline_scripts = doc
+        .select(&INLINE_SCRIPT_SEL)
+        .map(|el| el.text().collect::<String>())
+        .filter(|s| !s.trim().is_empty())
+        .collect();
+
+    let icons = doc.select(&ICON_SEL).filter_map(|el| attr(&el, "href")).collect();
+
+    let comments = doc
+        .tree
+        .values()
+        .filter_map(|node| match node {
+            Node::Comment(c) => Some(c.trim().to_string()),
+            _ => None,
+        })
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    HtmlInfo { title, meta, forms, links, scripts, icons, comments, inline_scripts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<!DOCTYPE html>
+<html><head>
+  <TITLE>  Admin
+     Login </TITLE>
+  <meta name="Generator" content="WordPress 6.4.2">
+  <meta property="og:site_name" content="Example">
+  <link rel="shortcut icon" href="/static/favicon.png">
+  <script src="/js/app.js"></script>
+  <script src="/js/app.js"></script>
+  <script>var config = {api: "/api/v1"};</script>
+  <script>   </script>
+</head><body>
+  <!-- TODO: remove debug endpoint /debug -->
+  <a href="/about">About</a> <a href=" /about ">Again</a> <a href="">Empty</a>
+  <iframe src="/embed"></iframe>
+  <form action="/login" method="post">
+    <input name="user"><input type="PASSWORD" name="pass"><select name="lang"></select>
+  </form>
+</body></html>"#;
+
+    #[test]
+    fn document_parts_are_extracted() {
+        let html = parse_html(PAGE);
+        assert_eq!(html.title.as_deref(), Some("Admin Login"));
+        assert_eq!(html.meta[0].name, "generator");
+        assert_eq!(html.meta[0].content, "WordPress 6.4.2");
+        assert_eq!(html.meta[1].name, "og:site_name");
+        assert_eq!(html.icons, ["/static/favicon.png"]);
+        assert_eq!(html.comments, ["TODO: remove debug endpoint /debug"]);
+        assert_eq!(html.inline_scripts.len(), 1);
+        assert!(html.inline_scripts[0].contains("/api/v1"));
+    }
+
+    #[test]
+    fn links_and_scripts_are_deduplicated_in_order() {
+        let html = parse_html(PAGE);
+        assert_eq!(html.links, ["/about", "/embed"]);
+        assert_eq!(html.scripts, ["/js/app.js"]);
+    }
+
+    #[test]
+    fn forms_and_password_inputs() {
+        let html = parse_html(PAGE);
+        let form = &html.forms[0];
+        assert_eq!(form.action.as_deref(), Some("/login"));
+        assert_eq!(form.method, "POST");
+        let types: Vec<&str> = form.inputs.iter().map(|i| i.input_type.as_str()).collect();
+        assert_eq!(types, ["text", "password", "select"]);
+        assert!(html.has_password_form());
+        assert!(!parse_html("<form><input name=q></form>").has_password_form());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::html_parser::HtmlInfo;
+use crate::prober::probe_url;
+use reqwest::{Client, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+
+// Bundles are large; banners and markers sit well within this
+const MAX_SCRIPT_SIZE: usize = 2 * 1024 * 1024;
+
+// Scripts fetched per page, in document order
+const MAX_SCRIPTS_PER_PAGE: usize = 10;
+
+// A fetched same-origin JavaScript file
+#[derive(Debug)]
+pub struct ScriptAsset {
+    pub url: String,
+    pub content: String,
+}
+
+// Lazily-fetched script, shared by every page that references it
+type ScriptSlot = Arc<OnceCell<Option<Arc<ScriptAsset>>>>;
+
+// Fetches each same-origin script once per scan, however many pages use it
+#[derive(Clone)]
+pub struct ScriptCache {
+    headers: Vec<(String, String)>,
+    permits: Arc<Semaphore>,  // The scan's rate limit, one permit per script request
+    scripts: Arc<Mutex<HashMap<String, ScriptSlot>>>,
+}
+
+impl ScriptCache {
+    pub fn new(headers: Vec<(String, String)>, permits: Arc<Semaphore>) -> ScriptCache {
+        ScriptCache { headers, permits, scripts: Arc::default() }
+    }
+
+    // The page's same-origin <script src> files (third-party CDNs are only
+    // matched by URL, never fetched)
+    pub async fn fetch_for(&self, client: &Client, page_url: &str, html: Option<&HtmlInfo>) -> Vec<Arc<ScriptAsset>> {
+        let (page, html) = match (Url::parse(page_url), html) {
+            (Ok(p), Some(h)) => (p, h),
+            _ => return vec![],
+        };
+        let same_origin: Vec<Url> = html
+            .scripts
+            .iter()
+            .filter_map(|src| page.join(src).ok())
+            .filter(|u| u.origin() == page.origin())
+            .take(MAX_SCRIPTS_PER_PAGE)
+            .collect();
+
+        let mut assets = vec![];
+        for url in same_origin {
+            let cell = self.scripts.lock().unwrap().entry(url.to_string()).or_default().clone();
+            if let Some(asset) = cell.get_or_init(|| self.fetch(client, url)).await {
+                assets.push(asset.clone());
+            }
+        }
+        assets
+    }
+
+    async fn fetch(&self, client: &Client, url: Url) -> Option<Arc<ScriptAsset>> {
+        let permit = self.permits.acquire().await.ok()?;
+        let result = probe_url(url.to_string(), client, "GET", None, self.headers.clone(), MAX_SCRIPT_SIZE).await.ok()?;
+        drop(permit);
+        // Missing bundles often come back as an HTML error page or a binary file
+        if !(200..300).contains(&result.status) || result.html.is_some() || result.body.is_empty() || result.is_binary() {
+            return None;
+        }
+        Some(Arc::new(ScriptAsset { content: result.body_text().into_owned(), url: result.url }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::html_parser::parse_html;
+    use crate::prober::test_server;
+    use std::sync::atomic::Ordering;
+
+    // Serves /big.js at 3 MB, /missing.js as an HTML error page, any other
+    // path as a one-line script naming itself
+    async fn server() -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        test_server(|path, _| match path {
+            "/big.js" => (200, "application/javascript".to_string(), vec![b'x'; 3 * 1024 * 1024]),
+            "/missing.js" => (404, "text/html".to_string(), b"<h1>Not Found</h1>".to_vec()),
+            _ => (200, "application/javascript".to_string(), format!("// {}", path).into_bytes()),
+        })
+        .await
+    }
+
+    fn client() -> Client {
+        Client::builder().no_proxy().build().unwrap()
+    }
+
+    fn cache() -> ScriptCache {
+        ScriptCache::new(vec![], Arc::new(Semaphore::new(1)))
+    }
+
+    fn page(srcs: &[&str]) -> HtmlInfo {
+        parse_html(&srcs.iter().map(|s| format!(r#"<script src="{}"></script>"#, s)).collect::<String>())
+    }
+
+    #[tokio::test]
+    async fn only_same_origin_scripts_are_fetched() {
+        let (base, served) = server().await;
+        let html = page(&["/a.js", "http://cdn.example/lib.js", "//other.example/b.js", "/missing.js"]);
+        let assets = cache().fetch_for(&client(), &format!("{}/app/", base), Some(&html)).await;
+        let urls: Vec<&str> = assets.iter().map(|a| a.url.as_str()).collect();
+        assert_eq!(urls, [format!("{}/a.js", base)]);
+        assert_eq!(assets[0].content, "// /a.js");
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn at_most_ten_scripts_per_page() {
+        let (base, served) = server().await;
+        let srcs: Vec<String> = (0..12).map(|i| format!("/s{}.js", i)).collect();
+        let html = page(&srcs.iter().map(String::as_str).collect::<Vec<_>>());
+        let assets = cache().fetch_for(&client(), &format!("{}/", base), Some(&html)).await;
+        assert_eq!(assets.len(), MAX_SCRIPTS_PER_PAGE);
+        assert_eq!(assets.last().unwrap().url, format!("{}/s9.js", base));
+        assert_eq!(served.load(Ordering::SeqCst), MAX_SCRIPTS_PER_PAGE);
+    }
+
+    #[tokio::test]
+    async fn large_scripts_are_cut_at_the_cap() {
+        let (base, _) = server().await;
+        let assets = cache().fetch_for(&client(), &format!("{}/", base), Some(&page(&["/big.js"]))).await;
+        assert_eq!(assets[0].content.len(), MAX_SCRIPT_SIZE);
+    }
+
+    #[tokio::test]
+    async fn shared_scripts_are_fetched_once() {
+        let (base, served) = server().await;
+        let (cache, client) = (cache(), client());
+        let first = cache.fetch_for(&client, &format!("{}/a", base), Some(&page(&["/app.js"]))).await;
+        let second = cache.fetch_for(&client, &format!("{}/b", base), Some(&page(&["/app.js", "/b.js"]))).await;
+        assert!(Arc::ptr_eq(&first[0], &second[0]));
+        assert_eq!(second.len(), 2);
+        assert_eq!(served.load(Ordering::SeqCst), 2);
+        assert_eq!(cache.permits.available_permits(), 1);
+    }
+}
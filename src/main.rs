@@ -1,26 +1,117 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 // Import modules (explicit for clarity, even if using crate::)
+mod active_fingerprint;
+mod anomaly;
 mod ai_engine;
+mod clustering;
+mod crawler;
+mod favicon;
 mod fuzzer;
+mod headers;
+mod html_parser;
+mod js_assets;
+mod js_extract;
+mod llm_triage;
+mod models;
+mod page_classifier;
+mod param_miner;
 mod prober;
+mod score_rules;
+mod secrets;
+#[cfg(feature = "ai-bert")]
+mod sentiment;
+mod signatures;
 mod tech_fingerprinter;
+mod vuln_db;
+mod waf_detector;
+mod wordlist_gen;
 
 #[derive(Parser, Debug)]
 #[command(name = "grimnir", version = "0.1.0", about = "A fused ffuf + httpx tool with AI smarts")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Base URL with FUZZ placeholder (e.g., http://example.com/FUZZ)
-    #[arg(short = 'u', long)]
-    url: String,
+    #[arg(short = 'u', long, required = true)]
+    url: Option<String>,
 
     /// Path to wordlist file
-    #[arg(short = 'w', long)]
-    wordlist: String,
+    #[arg(short = 'w', long, required = true)]
+    wordlist: Option<String>,
 
     /// Enable AI enhancements (e.g., response analysis)
     #[arg(long)]
     ai: bool,
 
+    /// Analyzers used by --ai, with optional weights (e.g. heuristics,sentiment:0.5); classifier, secrets, anomaly and
+    /// llm are opt-in (also added by the options that need them)
+    #[arg(long, default_value = ai_engine::DEFAULT_ANALYZERS)]
+    analyzers: String,
+
+    /// Local sentiment model directory (rust_model.ot, config.json, vocab.txt); see 'grimnir models fetch'
+    #[arg(long)]
+    model_dir: Option<String>,
+
+    /// How analyzer scores are combined: sum, max or mean
+    #[arg(long, default_value = "sum")]
+    score_formula: String,
+
+    /// Score rules file (JSON, same format as data/score_rules.json); rules replace bundled ones by ID
+    #[arg(long)]
+    score_rules: Option<String>,
+
+    /// Trained page classifier weights (JSON) used by the classifier analyzer on top of its heuristics
+    #[arg(long)]
+    classifier_model: Option<String>,
+
+    /// Extra secret detection rules (JSON, same format as data/secret_rules.json) for the secrets analyzer
+    #[arg(long)]
+    secret_rules: Option<String>,
+
+    /// OpenAI-compatible endpoint for LLM triage (e.g. http://localhost:11434/v1); adds the llm analyzer
+    #[arg(long)]
+    llm_endpoint: Option<String>,
+
+    /// Model name sent to the LLM endpoint
+    #[arg(long, default_value = "local")]
+    llm_model: String,
+
+    /// Bearer token for the LLM endpoint (default: $GRIMNIR_LLM_API_KEY)
+    #[arg(long)]
+    llm_api_key: Option<String>,
+
+    /// Maximum tokens of result summary sent per request
+    #[arg(long, default_value_t = 512)]
+    llm_prompt_tokens: usize,
+
+    /// Maximum tokens the model may reply with per request
+    #[arg(long, default_value_t = 200)]
+    llm_max_tokens: usize,
+
+    /// Maximum tokens spent on LLM triage over the whole scan
+    #[arg(long, default_value_t = 50000)]
+    llm_budget: usize,
+
+    /// Only results whose combined score (--score-formula) from the analyzers before llm is at least this are sent
+    #[arg(long, default_value_t = 0.5)]
+    llm_min_score: f32,
+
+    /// Seconds to wait for each LLM reply
+    #[arg(long, default_value_t = 60)]
+    llm_timeout: u64,
+
+    /// LLM verdict cache file, reused across scans (default: ~/.grimnir/llm_cache.jsonl)
+    #[arg(long)]
+    llm_cache: Option<String>,
+
+    /// Do not read or write the LLM verdict cache
+    #[arg(long)]
+    llm_no_cache: bool,
+
     /// Filter out specific status codes (comma-separated)
     #[arg(long, value_delimiter = ',', num_args = 1..)]
     filter_status: Option<Vec<u16>>,
@@ -64,12 +155,203 @@ struct Args {
     /// Regex patterns to filter out responses (repeatable)
     #[arg(long, num_args = 1..)]
     filter_regex: Vec<String>,
+
+    /// Filter out pages the classifier labels as these (comma-separated: soft-404, error, login, waf-block, real-content; needs --ai)
+    #[arg(long, value_delimiter = ',')]
+    filter_label: Vec<String>,
+
+    /// Only output results at least this anomalous for their host (0.0-1.0; needs --ai, adds the anomaly analyzer)
+    #[arg(long)]
+    match_anomaly: Option<f32>,
+
+    /// Maximum response body bytes to read (streamed; the rest is discarded)
+    #[arg(long, default_value_t = prober::DEFAULT_MAX_BODY)]
+    max_body: usize,
+
+    /// Store full responses in a directory, keyed by body hash (auto-named if no DIR)
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    store_responses: Option<String>,
+
+    /// Fetch and hash each host's favicon (Shodan mmh3 + MD5)
+    #[arg(long)]
+    favicon: bool,
+
+    /// Directory of extra fingerprint rules (Wappalyzer-style JSON/YAML), added to the bundled set
+    #[arg(long)]
+    signatures: Option<String>,
+
+    /// Attach known CVEs from the offline vulnerability database to versioned detections (needs --tech)
+    #[arg(long)]
+    cve: bool,
+
+    /// Vulnerability database path (default: ~/.grimnir/vulndb.json)
+    #[arg(long)]
+    vuln_db: Option<String>,
+
+    /// Fetch same-origin <script src> files and fingerprint JS libraries from their contents (needs --tech)
+    #[arg(long)]
+    fetch_js: bool,
+
+    /// Probe well-known paths once per host to confirm tech (needs --tech); FILE adds custom probes
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    active_tech: Option<String>,
+
+    /// Maximum active fingerprinting probe paths per host (plus one request for a random path)
+    #[arg(long, default_value_t = 14)]
+    active_budget: usize,
+
+    /// Detect WAFs/CDNs (pre-flight malicious-looking probe plus every response)
+    #[arg(long)]
+    waf: bool,
+
+    /// Requests per second to drop to once a WAF is detected (0 keeps --rate)
+    #[arg(long, default_value_t = 2)]
+    waf_rate: usize,
+
+    /// Group near-identical responses (same error/login page) and tag each result with a cluster ID
+    #[arg(long)]
+    cluster: bool,
+
+    /// Only output one representative per cluster (implies --cluster)
+    #[arg(long)]
+    collapse_clusters: bool,
+
+    /// Harvest words from responses (paths, JS identifiers, JSON keys, titles) and fuzz them in extra rounds
+    #[arg(long)]
+    learn: bool,
+
+    /// Extra rounds of harvested words with --learn
+    #[arg(long, default_value_t = 2)]
+    learn_rounds: usize,
+
+    /// Most harvested words fuzzed per round, best-ranked first
+    #[arg(long, default_value_t = 500)]
+    learn_max: usize,
+
+    /// Write every harvested word, best-ranked first, to a wordlist file
+    #[arg(long)]
+    save_wordlist: Option<String>,
+
+    /// Crawl the target first (links, forms, JS paths, robots.txt, sitemap.xml); crawled pages are probed like fuzz results
+    #[arg(long)]
+    crawl: bool,
+
+    /// Link levels followed from the start page with --crawl
+    #[arg(long, default_value_t = 3)]
+    crawl_depth: usize,
+
+    /// Most pages requested by the crawl
+    #[arg(long, default_value_t = 200)]
+    crawl_pages: usize,
+
+    /// Also fuzz under up to this many directories found by the crawl (needs a URL ending in /FUZZ)
+    #[arg(long, default_value_t = 0)]
+    crawl_roots: usize,
+
+    /// Extract endpoints, GraphQL operations and hard-coded keys from JavaScript (inline, JS responses and fetched
+    /// same-origin scripts); same-origin endpoints found are requested too
+    #[arg(long)]
+    js_extract: bool,
+
+    /// Discover hidden parameters instead of fuzzing (query, form, json); wordlist holds parameter names
+    #[arg(long)]
+    discover_params: Option<String>,
+
+    /// Candidate parameters sent per request in discovery mode
+    #[arg(long, default_value_t = 64)]
+    param_batch: usize,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage the offline vulnerability database
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+    /// Prepare local NLP model files (for air-gapped scanning)
+    Models {
+        #[command(subcommand)]
+        action: ModelsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelsCommand {
+    /// Download the sentiment model files for offline use
+    Fetch {
+        /// Target directory (default: ~/.grimnir/models/sentiment)
+        #[arg(long)]
+        model_dir: Option<String>,
+
+        /// Re-download files that are already present
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check that a model directory is complete and matches its recorded checksums
+    Verify {
+        /// Directory to check (default: ~/.grimnir/models/sentiment)
+        #[arg(long)]
+        model_dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DbCommand {
+    /// Import NVD JSON feeds (CVE API 2.0 or legacy 1.1, optionally .gz)
+    Import {
+        /// Feed files to import
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Vulnerability database path (default: ~/.grimnir/vulndb.json)
+        #[arg(long)]
+        db: Option<String>,
+    },
+}
+
+// Base directory for grimnir's local data: $GRIMNIR_HOME or ~/.grimnir
+pub fn grimnir_home() -> PathBuf {
+    std::env::var_os("GRIMNIR_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".grimnir")))
+        .unwrap_or_else(|| PathBuf::from(".grimnir"))
+}
+
+// Runs a maintenance subcommand instead of a scan
+async fn run_command(command: Command) {
+    let result = match command {
+        Command::Db { action: DbCommand::Import { files, db } } => {
+            let db_path = db.map(PathBuf::from).unwrap_or_else(vuln_db::default_path);
+            vuln_db::import(&files, &db_path)
+        }
+        Command::Models { action: ModelsCommand::Fetch { model_dir, force } } => {
+            let dir = model_dir.map(PathBuf::from).unwrap_or_else(models::default_dir);
+            models::fetch(&dir, force).await
+        }
+        Command::Models { action: ModelsCommand::Verify { model_dir } } => {
+            let dir = model_dir.map(PathBuf::from).unwrap_or_else(models::default_dir);
+            models::verify(&dir)
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
+    if let Some(command) = args.command {
+        run_command(command).await;
+        return;
+    }
+    // Both are required by clap whenever no subcommand is given
+    let url = args.url.expect("--url is required");
+    let wordlist = args.wordlist.expect("--wordlist is required");
+
     println!(r#"
 
   _______ .______       __  .___  ___. .__   __.  __  .______      
@@ -82,32 +364,160 @@ async fn main() {
     By: RowanDark v0.1.0 2025
     "#);
     println!("Grimnir is ready to probe and fuzz!");
-    println!("Target URL: {}", args.url);
-    println!("Wordlist: {}", args.wordlist);
+    println!("Target URL: {}", url);
+    println!("Wordlist: {}", wordlist);
     println!("AI enabled: {}", args.ai);
+    // Validate analyzers up front so a typo fails before any request is sent
+    let analyzers = if args.ai {
+        // An explicit --model-dir wins; otherwise a prepared default directory is used if present
+        let config = ai_engine::AnalyzerConfig {
+            model_dir: args.model_dir.map(PathBuf::from).or_else(|| Some(models::default_dir()).filter(|d| d.exists())),
+            classifier_model: args.classifier_model.map(PathBuf::from),
+            secret_rules: args.secret_rules.map(PathBuf::from),
+            score_rules: args.score_rules.map(PathBuf::from),
+            llm: args.llm_endpoint.clone().map(|endpoint| llm_triage::LlmConfig {
+                endpoint,
+                model: args.llm_model.clone(),
+                api_key: args.llm_api_key.clone().or_else(|| std::env::var("GRIMNIR_LLM_API_KEY").ok()),
+                prompt_tokens: args.llm_prompt_tokens,
+                max_tokens: args.llm_max_tokens,
+                budget: args.llm_budget,
+                min_score: args.llm_min_score,
+                timeout: args.llm_timeout,
+                cache: if args.llm_no_cache {
+                    None
+                } else {
+                    Some(args.llm_cache.clone().map(PathBuf::from).unwrap_or_else(|| grimnir_home().join("llm_cache.jsonl")))
+                },
+            }),
+        };
+        // Options that need an opt-in analyzer add it: label filters and a
+        // classifier model the classifier, secret rules the secrets analyzer,
+        // --match-anomaly the anomaly analyzer and an endpoint the llm
+        // analyzer; llm runs last so it sees the other analyzers' scores
+        let mut spec = args.analyzers.clone();
+        let has = |spec: &str, name: &str| spec.split(',').any(|e| e.split(':').next().unwrap_or("").trim().eq_ignore_ascii_case(name));
+        let implied = [
+            ("classifier", !args.filter_label.is_empty() || config.classifier_model.is_some()),
+            ("secrets", config.secret_rules.is_some()),
+            ("anomaly", args.match_anomaly.is_some()),
+            ("llm", config.llm.is_some()),
+        ];
+        for (name, needed) in implied {
+            if needed && !has(&spec, name) {
+                spec.push_str(&format!(",{}", name));
+            }
+        }
+        match ai_engine::AnalyzerRegistry::from_spec(&spec, &args.score_formula, &config) {
+            Ok(registry) => {
+                println!("Analyzers: {}", registry.names().join(", "));
+                Some(registry)
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    // Label filters depend on the classifier actually running
+    let mut filter_label = vec![];
+    for name in &args.filter_label {
+        match page_classifier::PageLabel::parse(name) {
+            Some(label) => filter_label.push(label),
+            None => {
+                eprintln!("Unknown page label '{}'. Use real-content, soft-404, error, login or waf-block.", name);
+                return;
+            }
+        }
+    }
+    if !filter_label.is_empty() && !analyzers.as_ref().is_some_and(|a| a.names().contains(&"classifier")) {
+        eprintln!("--filter-label needs --ai.");
+        return;
+    }
+    if args.match_anomaly.is_some() && analyzers.is_none() {
+        eprintln!("--match-anomaly needs --ai.");
+        return;
+    }
     println!("Method: {}", args.method);
     println!("Tech fingerprinting: {}", args.tech);
     if let Some(proxy) = &args.proxy {
         println!("Using proxy: {}", proxy);
     }
 
-    // Call the fuzzer with all params
+    // Parameter discovery is a separate mode built on the same prober
+    if let Some(location_str) = &args.discover_params {
+        let location = match param_miner::ParamLocation::parse(location_str) {
+            Some(l) => l,
+            None => {
+                eprintln!("Unknown parameter location '{}'. Use query, form or json.", location_str);
+                return;
+            }
+        };
+        println!("Parameter discovery: {:?}", location);
+        param_miner::discover(param_miner::DiscoverOptions {
+            base_url: url,
+            wordlist_path: wordlist,
+            location,
+            batch_size: args.param_batch,
+            rate: args.rate,
+            output: args.output,
+            method: args.method,
+            data: args.data,
+            raw_headers: args.header,
+            proxy_url: args.proxy,
+            proxy_auth: args.proxy_auth,
+            max_body: args.max_body,
+        }).await;
+        return;
+    }
+
     let concurrency = 10;  // Hardcoded for now; could make CLI arg later
-    fuzzer::fuzz(
-        args.url,
-        args.wordlist,
+    // Resolve the vulnerability database only when CVE matching is requested
+    let vuln_db_path = if args.cve {
+        Some(args.vuln_db.map(PathBuf::from).unwrap_or_else(vuln_db::default_path))
+    } else {
+        None
+    };
+
+    let options = fuzzer::FuzzOptions {
+        base_url: url,
+        wordlist_path: wordlist,
         concurrency,
-        args.ai,
-        args.filter_status,
-        args.filter_size,
-        args.rate,
-        args.output,
-        args.method,
-        args.data,
-        args.header,
-        args.tech,
-        args.proxy,
-        args.proxy_auth,
-        args.filter_regex,
-    ).await;
+        filter_status: args.filter_status,
+        filter_size: args.filter_size,
+        rate: args.rate,
+        output: args.output,
+        method: args.method,
+        data: args.data,
+        raw_headers: args.header,
+        tech_enabled: args.tech,
+        proxy_url: args.proxy,
+        proxy_auth: args.proxy_auth,
+        filter_regex: args.filter_regex,
+        filter_label,
+        match_anomaly: args.match_anomaly,
+        max_body: args.max_body,
+        store_responses: args.store_responses,
+        favicon_enabled: args.favicon,
+        signatures_dir: args.signatures,
+        vuln_db_path,
+        fetch_js: args.fetch_js,
+        active_probes: args.active_tech,
+        active_budget: args.active_budget,
+        waf_enabled: args.waf,
+        waf_rate: args.waf_rate,
+        cluster_enabled: args.cluster || args.collapse_clusters,
+        collapse_clusters: args.collapse_clusters,
+        learn_rounds: if args.learn { args.learn_rounds } else { 0 },
+        learn_max: args.learn_max,
+        save_wordlist: args.save_wordlist,
+        crawl_enabled: args.crawl,
+        crawl_depth: args.crawl_depth,
+        crawl_pages: args.crawl_pages,
+        crawl_roots: args.crawl_roots,
+        js_extract: args.js_extract,
+    };
+    fuzzer::fuzz(options, analyzers).await;
 }
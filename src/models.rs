@@ -0,0 +1,207 @@
+use reqwest::Client;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// One file of a rust-bert model directory and where it is published
+pub struct ModelFile {
+    pub name: &'static str,
+    pub url: &'static str,
+}
+
+// DistilBERT fine-tuned on SST-2: the model rust-bert's sentiment pipeline uses by default
+pub const SENTIMENT_FILES: [ModelFile; 3] = [
+    ModelFile {
+        name: "rust_model.ot",
+        url: "https://huggingface.co/distilbert-base-uncased-finetuned-sst-2-english/resolve/main/rust_model.ot",
+    },
+    ModelFile {
+        name: "config.json",
+        url: "https://huggingface.co/distilbert-base-uncased-finetuned-sst-2-english/resolve/main/config.json",
+    },
+    ModelFile {
+        name: "vocab.txt",
+        url: "https://huggingface.co/bert-base-uncased/resolve/main/vocab.txt",
+    },
+];
+
+// sha256sum-style manifest written by fetch and checked by verify
+const CHECKSUMS_FILE: &str = "checksums.sha256";
+
+// Default model location: $GRIMNIR_HOME or ~/.grimnir, then models/sentiment
+pub fn default_dir() -> PathBuf {
+    crate::grimnir_home().join("models").join("sentiment")
+}
+
+// Names of the model files not present (or empty) in a directory
+pub fn missing_files(dir: &Path) -> Vec<&'static str> {
+    SENTIMENT_FILES
+        .iter()
+        .filter(|f| fs::metadata(dir.join(f.name)).map_or(true, |m| m.len() == 0))
+        .map(|f| f.name)
+        .collect()
+}
+
+fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn read_checksums(dir: &Path) -> Option<HashMap<String, String>> {
+    let content = fs::read_to_string(dir.join(CHECKSUMS_FILE)).ok()?;
+    Some(
+        content
+            .lines()
+            .filter_map(|l| l.split_once("  "))
+            .map(|(hash, name)| (name.trim().to_string(), hash.trim().to_lowercase()))
+            .collect(),
+    )
+}
+
+// Streams one file to disk (via a .part file, so an interrupted download
+// never looks complete) and returns its SHA-256
+async fn download(client: &Client, url: &str, dest: &Path) -> Result<String, String> {
+    let mut res = client.get(url).send().await.map_err(|e| format!("Download of {} failed: {}", url, e))?;
+    if !res.status().is_success() {
+        return Err(format!("Download of {} failed: HTTP {}", url, res.status()));
+    }
+    let part = dest.with_extension("part");
+    let mut file = File::create(&part).map_err(|e| format!("Cannot create '{}': {}", part.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut written: u64 = 0;
+    while let Some(chunk) = res.chunk().await.map_err(|e| format!("Download of {} failed: {}", url, e))? {
+        file.write_all(&chunk).map_err(|e| format!("Cannot write '{}': {}", part.display(), e))?;
+        hasher.update(&chunk);
+        written += chunk.len() as u64;
+    }
+    fs::rename(&part, dest).map_err(|e| format!("Cannot move '{}' into place: {}", part.display(), e))?;
+    println!("  {} ({} bytes)", dest.display(), written);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Downloads the sentiment model into a directory for offline use; files
+// already present are kept unless `force` is set
+pub async fn fetch(dir: &Path, force: bool) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Cannot create '{}': {}", dir.display(), e))?;
+    // No overall timeout: the model weights are a few hundred MB
+    let client = Client::builder()
+        .user_agent("Grimnir/0.1")
+        .build()
+        .map_err(|e| format!("Cannot build HTTP client: {}", e))?;
+
+    let mut checksums = read_checksums(dir).unwrap_or_default();
+    println!("Fetching sentiment model into '{}'", dir.display());
+    for file in &SENTIMENT_FILES {
+        let dest = dir.join(file.name);
+        if !force && !missing_files(dir).contains(&file.name) {
+            println!("  {} already present, skipping (use --force to re-download)", dest.display());
+            if !checksums.contains_key(file.name) {
+                checksums.insert(file.name.to_string(), sha256_file(&dest)?);
+            }
+            continue;
+        }
+        let hash = download(&client, file.url, &dest).await?;
+        checksums.insert(file.name.to_string(), hash);
+    }
+
+    let manifest: String = SENTIMENT_FILES
+        .iter()
+        .filter_map(|f| checksums.get(f.name).map(|h| format!("{}  {}\n", h, f.name)))
+        .collect();
+    fs::write(dir.join(CHECKSUMS_FILE), manifest).map_err(|e| format!("Cannot write checksums: {}", e))?;
+    println!("Model ready. Scan offline with: grimnir ... --ai --model-dir {}", dir.display());
+    Ok(())
+}
+
+// Checks that a model directory is complete and unmodified since fetch
+pub fn verify(dir: &Path) -> Result<(), String> {
+    let missing = missing_files(dir);
+    if !missing.is_empty() {
+        return Err(format!(
+            "Model directory '{}' is missing: {}. Run 'grimnir models fetch --model-dir {}' on a connected machine and copy it over.",
+            dir.display(),
+            missing.join(", "),
+            dir.display()
+        ));
+    }
+    let config = fs::read_to_string(dir.join("config.json")).map_err(|e| format!("Cannot read config.json: {}", e))?;
+    serde_json::from_str::<serde_json::Value>(&config).map_err(|e| format!("config.json is not valid JSON: {}", e))?;
+
+    match read_checksums(dir) {
+        Some(expected) => {
+            for file in &SENTIMENT_FILES {
+                let actual = sha256_file(&dir.join(file.name))?;
+                match expected.get(file.name) {
+                    Some(hash) if *hash == actual => println!("  {}: OK", file.name),
+                    Some(_) => return Err(format!("{} does not match its recorded checksum (corrupt or replaced)", file.name)),
+                    None => println!("  {}: present (no recorded checksum)", file.name),
+                }
+            }
+        }
+        None => println!("No {} in '{}'; only checked that the files exist.", CHECKSUMS_FILE, dir.display()),
+    }
+    println!("Model directory '{}' is complete.", dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh, empty directory per test under the system temp dir
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("grimnir-models-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_model(dir: &Path) {
+        fs::write(dir.join("rust_model.ot"), b"weights").unwrap();
+        fs::write(dir.join("config.json"), r#"{"dim": 768}"#).unwrap();
+        fs::write(dir.join("vocab.txt"), "[PAD]\n[UNK]\n").unwrap();
+    }
+
+    #[test]
+    fn missing_and_empty_files_are_reported() {
+        let dir = scratch("missing");
+        fs::write(dir.join("config.json"), "").unwrap();
+        fs::write(dir.join("vocab.txt"), "[PAD]\n").unwrap();
+        assert_eq!(missing_files(&dir), ["rust_model.ot", "config.json"]);
+        assert!(verify(&dir).unwrap_err().contains("missing: rust_model.ot, config.json"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checksums_are_verified() {
+        let dir = scratch("checksums");
+        write_model(&dir);
+        // Without a manifest only presence is checked
+        assert!(verify(&dir).is_ok());
+
+        let manifest: String = SENTIMENT_FILES
+            .iter()
+            .map(|f| format!("{}  {}\n", sha256_file(&dir.join(f.name)).unwrap().to_uppercase(), f.name))
+            .collect();
+        fs::write(dir.join(CHECKSUMS_FILE), manifest).unwrap();
+        assert_eq!(read_checksums(&dir).unwrap().len(), 3);
+        assert!(verify(&dir).is_ok());
+
+        fs::write(dir.join("vocab.txt"), "[PAD]\n[CLS]\n").unwrap();
+        assert!(verify(&dir).unwrap_err().starts_with("vocab.txt does not match"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let dir = scratch("config");
+        write_model(&dir);
+        fs::write(dir.join("config.json"), "<html>404</html>").unwrap();
+        assert!(verify(&dir).unwrap_err().starts_with("config.json is not valid JSON"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,442 @@
+use crate::ai_engine::{done, Analysis, Analyzer, AnalyzerFuture};
+use crate::clustering::{content_tokens, signature, similar, PageSignature};
+use crate::prober::ProbeResult;
+use crate::signatures::combine_confidence;
+use crate::waf_detector::BLOCK_STATUSES;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// Below this confidence no label wins and the page counts as real content
+const LABEL_THRESHOLD: u8 = 50;
+
+// Bytes a page may differ from its baseline's predicted length and still match
+const LENGTH_TOLERANCE: u64 = 8;
+
+// Only the start of the body is searched for phrases; error pages are short
+const PHRASE_SCAN_BYTES: usize = 64 * 1024;
+
+// "Not found" wording, several languages; matched lowercase in title and text
+const NOT_FOUND_PHRASES: &[&str] = &[
+    "not found", "cannot be found", "could not be found", "can't be found", "doesn't exist", "does not exist",
+    "no longer exists", "no longer available", "nothing here", "nicht gefunden", "existiert nicht", "introuvable",
+    "n'existe pas", "non trouvée", "no encontrad", "no existe", "non trovat", "non esiste", "não encontrad",
+    "não existe", "niet gevonden", "bestaat niet", "nie znaleziono", "nie istnieje", "не найден", "не существует",
+    "找不到", "页面不存在", "未找到", "見つかりません", "存在しません", "찾을 수 없", "bulunamadı", "ei löytynyt",
+    "hittades inte", "ikke fundet", "finnes ikke", "nenalezen", "nem található", "δεν βρέθηκε", "غير موجود", "לא נמצא",
+];
+
+// Framework error pages and stack traces: strong evidence even on a 200
+const STACK_TRACE_PHRASES: &[&str] = &[
+    "traceback (most recent call last)", "exception in thread", "unhandled exception", "server error in '/' application",
+    "whitelabel error page", "stack trace:", "fatal error: uncaught", "at java.", "at org.springframework.",
+];
+
+// Server and application error wording
+const ERROR_PHRASES: &[&str] = &[
+    "internal server error", "an error occurred", "an error has occurred", "unexpected error", "something went wrong",
+    "service unavailable", "bad gateway", "gateway timeout", "fatal error", "interner serverfehler", "ein fehler ist aufgetreten", "erreur interne",
+    "une erreur est survenue", "error interno", "se ha producido un error", "errore interno", "si è verificato un errore",
+    "erro interno", "ocorreu um erro", "внутренняя ошибка сервера", "произошла ошибка", "服务器内部错误", "内部サーバーエラー",
+];
+
+// Sign-in wording; only trusted in titles or next to a form
+const LOGIN_PHRASES: &[&str] = &[
+    "log in", "login", "sign in", "signin", "log on", "anmelden", "einloggen", "connexion", "se connecter",
+    "iniciar sesión", "acceder", "accedi", "entrar", "inloggen", "zaloguj", "войти", "вход", "登录", "登入", "ログイン",
+    "로그인", "giriş yap",
+];
+
+// Block-page wording shared by WAFs and CDNs
+const BLOCK_PHRASES: &[&str] = &[
+    "access denied", "request blocked", "request rejected", "has been blocked", "you have been blocked",
+    "attention required", "web application firewall", "security policy", "incident id", "support id",
+    "suspicious activity", "forbidden by administrative rules", "zugriff verweigert", "accès refusé",
+    "acceso denegado", "accesso negato", "доступ запрещен", "访问被拒绝",
+];
+
+// What kind of page a response is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum PageLabel {
+    #[serde(rename = "real-content")]
+    RealContent,
+    #[serde(rename = "soft-404")]
+    Soft404,
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "login")]
+    Login,
+    #[serde(rename = "waf-block")]
+    WafBlock,
+}
+
+// Tie-break order when several labels score the same: most specific first
+const LABEL_ORDER: [PageLabel; 4] = [PageLabel::WafBlock, PageLabel::Login, PageLabel::Error, PageLabel::Soft404];
+
+impl PageLabel {
+    pub fn parse(s: &str) -> Option<PageLabel> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "real-content" | "real" | "content" => Some(PageLabel::RealContent),
+            "soft-404" | "soft404" => Some(PageLabel::Soft404),
+            "error" => Some(PageLabel::Error),
+            "login" => Some(PageLabel::Login),
+            "waf-block" | "waf" | "blocked" => Some(PageLabel::WafBlock),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PageLabel::RealContent => "real-content",
+            PageLabel::Soft404 => "soft-404",
+            PageLabel::Error => "error",
+            PageLabel::Login => "login",
+            PageLabel::WafBlock => "waf-block",
+        }
+    }
+
+    // Score component for pages that are rarely worth triaging
+    fn score_adjustment(&self) -> Option<(&'static str, f32)> {
+        match self {
+            PageLabel::Soft404 => Some(("soft_404", -0.5)),
+            PageLabel::WafBlock => Some(("waf_block", -0.5)),
+            PageLabel::Error => Some(("error_page", -0.3)),
+            PageLabel::RealContent | PageLabel::Login => None,
+        }
+    }
+}
+
+// The label a response got, how sure the classifier is and why
+#[derive(Debug, Clone, Serialize)]
+pub struct Classification {
+    pub label: PageLabel,
+    pub confidence: u8,  // 0-100
+    pub reasons: Vec<String>,
+}
+
+// Optional trained model: per-label logistic regression over the page's
+// normalised words, e.g. {"labels": {"soft-404": {"bias": -2.0, "weights": {"found": 1.5}}}}
+#[derive(Deserialize)]
+struct ModelFile {
+    labels: HashMap<String, LabelWeights>,
+}
+
+#[derive(Deserialize)]
+struct LabelWeights {
+    #[serde(default)]
+    bias: f32,
+    #[serde(default)]
+    weights: HashMap<String, f32>,
+}
+
+struct LinearModel {
+    labels: Vec<(PageLabel, LabelWeights)>,
+}
+
+impl LinearModel {
+    fn load(path: &Path) -> Result<LinearModel, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Cannot read classifier model '{}': {}", path.display(), e))?;
+        let file: ModelFile = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid classifier model '{}': {}", path.display(), e))?;
+        let mut labels = vec![];
+        for (name, weights) in file.labels {
+            let label = PageLabel::parse(&name)
+                .ok_or_else(|| format!("Unknown label '{}' in classifier model '{}'", name, path.display()))?;
+            labels.push((label, weights));
+        }
+        Ok(LinearModel { labels })
+    }
+
+    // Probability (0-100) of each label the model knows
+    fn predict(&self, words: &HashSet<String>) -> Vec<(PageLabel, u8)> {
+        self.labels
+            .iter()
+            .map(|(label, w)| {
+                let z = w.bias + words.iter().filter_map(|word| w.weights.get(word)).sum::<f32>();
+                (*label, (100.0 / (1.0 + (-z).exp())).round() as u8)
+            })
+            .collect()
+    }
+}
+
+// What a request for a nonexistent word looks like on this target
+struct Baseline {
+    status: u16,
+    body_len: u64,
+    url_len: u64,
+    echoes: u64,  // Times the word was reflected in the body
+    signature: PageSignature,
+}
+
+// Labels responses as real content, soft-404, error, login or WAF block from
+// status, multilingual phrases, calibration baselines and an optional model
+pub struct PageClassifier {
+    baselines: Vec<Baseline>,
+    model: Option<LinearModel>,
+}
+
+impl PageClassifier {
+    pub fn new(model_path: Option<&Path>) -> Result<PageClassifier, String> {
+        let model = match model_path {
+            Some(path) => Some(LinearModel::load(path)?),
+            None => None,
+        };
+        Ok(PageClassifier { baselines: vec![], model })
+    }
+
+    pub fn classify(&self, result: &ProbeResult) -> Classification {
+        let mut signals: HashMap<PageLabel, Vec<(u8, String)>> = HashMap::new();
+        let mut add = |label: PageLabel, confidence: u8, reason: String| signals.entry(label).or_default().push((confidence, reason));
+
+        let title = result.title.as_deref().unwrap_or("").to_lowercase();
+        let body = result.body_text();
+        let text = if result.is_binary() { String::new() } else { clip_bytes(&body, PHRASE_SCAN_BYTES).to_lowercase() };
+
+        // Status codes
+        match result.status {
+            404 | 410 => add(PageLabel::Error, 90, format!("HTTP {} not found", result.status)),
+            401 | 407 => add(PageLabel::Login, 85, format!("HTTP {} authentication required", result.status)),
+            s if s >= 500 && !BLOCK_STATUSES.contains(&s) => add(PageLabel::Error, 90, format!("HTTP {} server error", s)),
+            s if (400..500).contains(&s) && !BLOCK_STATUSES.contains(&s) => add(PageLabel::Error, 70, format!("HTTP {} client error", s)),
+            _ => {}
+        }
+
+        // WAF/CDN block pages
+        if let Some(waf) = &result.waf {
+            if BLOCK_STATUSES.contains(&result.status) {
+                add(PageLabel::WafBlock, waf.confidence.max(LABEL_THRESHOLD), format!("{} block (HTTP {})", waf.vendor, result.status));
+            }
+        }
+        if let Some(phrase) = find_phrase(&text, BLOCK_PHRASES) {
+            let confidence = if BLOCK_STATUSES.contains(&result.status) { 60 } else { 20 };
+            add(PageLabel::WafBlock, confidence, format!("block wording \"{}\"", phrase));
+        }
+
+        // Login walls
+        if result.html.as_ref().is_some_and(|h| h.has_password_form()) {
+            add(PageLabel::Login, 80, "password form".to_string());
+        }
+        if let Some(phrase) = find_phrase(&title, LOGIN_PHRASES) {
+            add(PageLabel::Login, 60, format!("title \"{}\"", phrase));
+        } else if let (Some(phrase), true) = (find_phrase(&text, LOGIN_PHRASES), result.html.as_ref().is_some_and(|h| !h.forms.is_empty())) {
+            add(PageLabel::Login, 30, format!("form with \"{}\"", phrase));
+        }
+
+        // Error pages served with any status
+        if let Some(phrase) = find_phrase(&text, STACK_TRACE_PHRASES) {
+            add(PageLabel::Error, 60, format!("stack trace \"{}\"", phrase));
+        }
+        if let Some(phrase) = find_phrase(&title, ERROR_PHRASES) {
+            add(PageLabel::Error, 60, format!("title \"{}\"", phrase));
+        } else if let Some(phrase) = find_phrase(&text, ERROR_PHRASES) {
+            add(PageLabel::Error, 40, format!("error wording \"{}\"", phrase));
+        }
+
+        // Soft-404s: "not found" pages that don't say so in the status
+        if !matches!(result.status, 404 | 410) {
+            if let Some(phrase) = find_phrase(&title, NOT_FOUND_PHRASES).or_else(|| title.contains("404").then_some("404")) {
+                add(PageLabel::Soft404, 70, format!("title \"{}\"", phrase));
+            } else if let Some(phrase) = find_phrase(&text, NOT_FOUND_PHRASES) {
+                add(PageLabel::Soft404, 40, format!("not-found wording \"{}\"", phrase));
+            }
+            let sig = signature(result);
+            for base in &self.baselines {
+                if similar(&base.signature, &sig) {
+                    add(PageLabel::Soft404, 85, "same as calibration baseline".to_string());
+                } else if base.status == result.status && base.matches_length(result) {
+                    // Supporting evidence only: real pages can share a length by chance
+                    add(PageLabel::Soft404, 40, format!("length matches baseline ({} bytes)", result.body_len));
+                }
+            }
+        }
+
+        // Per-label heuristic confidence, then the model's opinion on top
+        let mut scores: Vec<(PageLabel, u8, Vec<String>)> = LABEL_ORDER
+            .iter()
+            .chain([PageLabel::RealContent].iter())
+            .map(|label| {
+                let mut hits = signals.remove(label).unwrap_or_default();
+                let mut seen = HashSet::new();
+                hits.retain(|(_, reason)| seen.insert(reason.clone()));
+                let confidence = combine_confidence(hits.iter().map(|(c, _)| *c));
+                (*label, confidence, hits.into_iter().map(|(_, r)| r).collect())
+            })
+            .collect();
+        if let Some(model) = &self.model {
+            let mut words: HashSet<String> = content_tokens(result, &body).into_iter().collect();
+            words.extend(title.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_string));
+            for (label, p) in model.predict(&words) {
+                if let Some(entry) = scores.iter_mut().find(|(l, _, _)| *l == label) {
+                    entry.1 = combine_confidence([entry.1, p].into_iter());
+                    entry.2.push(format!("model {}%", p));
+                }
+            }
+        }
+
+        // Highest score wins (ties go to the earlier, more specific label)
+        let best = scores.iter().enumerate().max_by_key(|(i, (_, c, _))| (*c, std::cmp::Reverse(*i))).map(|(_, s)| s.clone());
+        match best {
+            Some((label, confidence, reasons)) if confidence >= LABEL_THRESHOLD => Classification { label, confidence, reasons },
+            best => Classification {
+                label: PageLabel::RealContent,
+                confidence: 100 - best.map_or(0, |(_, c, _)| c),
+                reasons: vec![],
+            },
+        }
+    }
+}
+
+impl Baseline {
+    // Catch-all pages that echo the word grow or shrink with its length
+    fn matches_length(&self, result: &ProbeResult) -> bool {
+        if result.body_truncated || self.body_len == 0 {
+            return false;
+        }
+        let word_delta = result.url.len() as i64 - self.url_len as i64;
+        let expected = self.body_len as i64 + self.echoes as i64 * word_delta;
+        result.body_len.abs_diff(expected.max(0) as usize) as u64 <= LENGTH_TOLERANCE
+    }
+}
+
+impl Analyzer for PageClassifier {
+    fn name(&self) -> &'static str {
+        "classifier"
+    }
+
+    fn needs_calibration(&self) -> bool {
+        true
+    }
+
+    fn calibrate(&mut self, baselines: &[(String, ProbeResult)]) {
+        self.baselines = baselines
+            .iter()
+            .map(|(word, result)| Baseline {
+                status: result.status,
+                body_len: result.body_len as u64,
+                url_len: result.url.len() as u64,
+                echoes: result.body_text().matches(word.as_str()).count() as u64,
+                signature: signature(result),
+            })
+            .collect();
+    }
+
+    fn analyze<'a>(&'a self, result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a> {
+        let classification = self.classify(result);
+        if classification.label != PageLabel::RealContent {
+            let insight = format!(
+                "Classified as {} ({}%): {}",
+                classification.label.as_str(),
+                classification.confidence,
+                classification.reasons.join(", ")
+            );
+            match classification.label.score_adjustment() {
+                Some((component, value)) => analysis.add(self.name(), component, value, insight),
+                None => analysis.insights.push(insight),
+            }
+        }
+        analysis.label = Some(classification);
+        done()
+    }
+}
+
+fn find_phrase(text: &str, phrases: &[&'static str]) -> Option<&'static str> {
+    phrases.iter().find(|p| text.contains(*p)).copied()
+}
+
+// Longest prefix of at most `max` bytes that ends on a char boundary
+fn clip_bytes(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+
+    fn page(url: &str, status: u16, body: &str) -> ProbeResult {
+        test_response(url, status, &[("content-type", "text/html")], body)
+    }
+
+    fn label(classifier: &PageClassifier, result: &ProbeResult) -> PageLabel {
+        classifier.classify(result).label
+    }
+
+    // A catch-all that answers every path with 200 and echoes it
+    fn catch_all(word: &str) -> String {
+        format!("<html><body><h2>Oops</h2><p>We looked everywhere for {} but came up empty.</p></body></html>", word)
+    }
+
+    #[test]
+    fn labels_parse_loosely() {
+        assert_eq!(PageLabel::parse(" Soft_404 "), Some(PageLabel::Soft404));
+        assert_eq!(PageLabel::parse("waf"), Some(PageLabel::WafBlock));
+        assert_eq!(PageLabel::parse("teapot"), None);
+        for label in LABEL_ORDER {
+            assert_eq!(PageLabel::parse(label.as_str()), Some(label));
+        }
+    }
+
+    #[test]
+    fn status_and_wording() {
+        let classifier = PageClassifier::new(None).unwrap();
+        assert_eq!(label(&classifier, &page("http://t/a", 404, "<h1>Not Found</h1>")), PageLabel::Error);
+        assert_eq!(label(&classifier, &page("http://t/a", 200, "<title>Page not found</title>")), PageLabel::Soft404);
+        assert_eq!(label(&classifier, &page("http://t/a", 200, "<title>Seite nicht gefunden</title>")), PageLabel::Soft404);
+        let trace = "<pre>Traceback (most recent call last):\n  File \"app.py\"</pre>";
+        assert_eq!(label(&classifier, &page("http://t/a", 200, trace)), PageLabel::Error);
+        let login = r#"<title>Welcome</title><form><input name="u"><input type="password" name="p"></form>"#;
+        assert_eq!(label(&classifier, &page("http://t/a", 200, login)), PageLabel::Login);
+        assert_eq!(label(&classifier, &page("http://t/a", 401, "")), PageLabel::Login);
+        let blocked = "<title>Access denied</title><p>This request was blocked by the security policy. Incident ID: 42</p>";
+        assert_eq!(label(&classifier, &page("http://t/a", 403, blocked)), PageLabel::WafBlock);
+        let real = page("http://t/about", 200, "<title>About us</title><p>We build bridges since 1920.</p>");
+        let classification = classifier.classify(&real);
+        assert_eq!(classification.label, PageLabel::RealContent);
+        assert_eq!(classification.confidence, 100);
+    }
+
+    #[test]
+    fn calibration_baselines_catch_soft_404s() {
+        let mut classifier = PageClassifier::new(None).unwrap();
+        let baselines: Vec<(String, ProbeResult)> = ["xq7kz2", "mv93tp"]
+            .iter()
+            .map(|w| (w.to_string(), page(&format!("http://t/{}", w), 200, &catch_all(w))))
+            .collect();
+        classifier.calibrate(&baselines);
+        let miss = page("http://t/old-backup", 200, &catch_all("old-backup"));
+        let classification = classifier.classify(&miss);
+        assert_eq!(classification.label, PageLabel::Soft404);
+        assert!(classification.reasons.contains(&"same as calibration baseline".to_string()));
+        assert!(classifier.baselines[0].matches_length(&miss));
+        let real = page("http://t/team", 200, "<title>Team</title><p>Our people and what they work on.</p>");
+        assert_eq!(label(&classifier, &real), PageLabel::RealContent);
+    }
+
+    #[test]
+    fn model_adds_its_opinion() {
+        let mut weights = HashMap::new();
+        weights.insert("oops".to_string(), 4.0);
+        let classifier = PageClassifier {
+            baselines: vec![],
+            model: Some(LinearModel { labels: vec![(PageLabel::Soft404, LabelWeights { bias: -2.0, weights })] }),
+        };
+        let classification = classifier.classify(&page("http://t/x", 200, &catch_all("x")));
+        assert_eq!(classification.label, PageLabel::Soft404);
+        assert_eq!(classification.reasons, ["model 88%"]);
+    }
+
+    #[test]
+    fn clipping_respects_char_boundaries() {
+        assert_eq!(clip_bytes("héllo", 2), "h");
+        assert_eq!(clip_bytes("héllo", 3), "hé");
+        assert_eq!(clip_bytes("abc", 10), "abc");
+    }
+}
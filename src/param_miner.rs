@@ -0,0 +1,418 @@
+use crate::fuzzer::{build_client, load_wordlist, parse_headers};
+use crate::prober::{probe_url, ProbeResult};
+use chrono::Local;
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::Write;
+use std::time::Duration;
+
+// Where candidate parameters are injected
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamLocation {
+    Query,
+    Form,
+    Json,
+}
+
+impl ParamLocation {
+    pub fn parse(s: &str) -> Option<ParamLocation> {
+        match s.to_lowercase().as_str() {
+            "query" | "get" => Some(ParamLocation::Query),
+            "form" | "post" => Some(ParamLocation::Form),
+            "json" => Some(ParamLocation::Json),
+            _ => None,
+        }
+    }
+}
+
+// A confirmed hidden parameter and why it was flagged
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredParam {
+    pub name: String,
+    pub location: ParamLocation,
+    pub reason: String,
+}
+
+// Baseline response signature that each batch is compared against
+struct Baseline {
+    status: u16,
+    body_len: usize,
+    len_tolerance: usize,  // Natural jitter seen between two identical requests
+    title: Option<String>,
+}
+
+// Generates a short random token used as a parameter value (reflection marker)
+fn random_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(Local::now().timestamp_nanos_opt().unwrap_or_default() as u64);
+    format!("grm{:08x}", hasher.finish() as u32)
+}
+
+// application/x-www-form-urlencoded pairs, so names with '&', '=', '#', '+',
+// spaces or non-ASCII characters stay one parameter (url's serializer, via a
+// scratch URL as the url crate is only reachable through reqwest)
+fn encode_pairs(pairs: &[(String, String)]) -> String {
+    let mut scratch = Url::parse("http://localhost/").unwrap();
+    scratch.query_pairs_mut().extend_pairs(pairs);
+    scratch.query().unwrap_or("").to_string()
+}
+
+// Builds URL and body for one batch of (name, value) pairs
+fn build_request(
+    base_url: &str,
+    base_data: &Option<String>,
+    location: ParamLocation,
+    batch: &[(String, String)],
+) -> (String, Option<String>) {
+    match location {
+        ParamLocation::Query => {
+            if batch.is_empty() {
+                return (base_url.to_string(), base_data.clone());
+            }
+            // Appended after the existing query, which is left as the user wrote it
+            let url = match Url::parse(base_url) {
+                Ok(mut url) => {
+                    url.query_pairs_mut().extend_pairs(batch);
+                    url.to_string()
+                }
+                Err(_) => {
+                    let sep = if base_url.contains('?') { '&' } else { '?' };
+                    format!("{}{}{}", base_url, sep, encode_pairs(batch))
+                }
+            };
+            (url, base_data.clone())
+        }
+        ParamLocation::Form => {
+            let mut pairs: Vec<String> = base_data.iter().filter(|d| !d.is_empty()).cloned().collect();
+            if !batch.is_empty() {
+                pairs.push(encode_pairs(batch));
+            }
+            (base_url.to_string(), Some(pairs.join("&")))
+        }
+        ParamLocation::Json => {
+            // Merge into the user-supplied object when it parses, otherwise start fresh
+            let mut obj = base_data
+                .as_ref()
+                .and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+                .and_then(|v| v.as_object().cloned())
+                .unwrap_or_default();
+            for (k, v) in batch {
+                obj.insert(k.clone(), serde_json::Value::String(v.clone()));
+            }
+            (base_url.to_string(), Some(serde_json::Value::Object(obj).to_string()))
+        }
+    }
+}
+
+// Content type for body locations, sent with every request so the baseline
+// (an empty body when there is no --data) goes out like the batches do.
+// A Content-Type given with -H is left as it is.
+fn with_content_type(mut headers: Vec<(String, String)>, location: ParamLocation) -> Vec<(String, String)> {
+    let content_type = match location {
+        ParamLocation::Query => return headers,
+        ParamLocation::Form => "application/x-www-form-urlencoded",
+        ParamLocation::Json => "application/json",
+    };
+    if !headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-type")) {
+        headers.push(("Content-Type".to_string(), content_type.to_string()));
+    }
+    headers
+}
+
+// Compares a batch response with the baseline; returns a reason if it differs
+fn compare(baseline: &Baseline, result: &ProbeResult) -> Option<String> {
+    if result.status != baseline.status {
+        return Some(format!("status changed {} -> {}", baseline.status, result.status));
+    }
+    if result.body_len.abs_diff(baseline.body_len) > baseline.len_tolerance {
+        return Some(format!("length changed {} -> {}", baseline.body_len, result.body_len));
+    }
+    if result.title != baseline.title {
+        return Some("title changed".to_string());
+    }
+    None
+}
+
+// Finds which parameter values were reflected in the response body
+fn reflected<'a>(result: &ProbeResult, batch: &'a [(String, String)]) -> Vec<&'a str> {
+    let body = result.body_text();
+    batch.iter().filter(|(_, v)| body.contains(v.as_str())).map(|(k, _)| k.as_str()).collect()
+}
+
+// What a batch that changed the response comes down to
+#[derive(Debug, PartialEq)]
+enum Narrowed {
+    Found(String),                     // One name left: it caused the change
+    Halves(Vec<String>, Vec<String>),  // Test each half again, first half first
+    Explained,                         // Every name was already identified by reflection
+}
+
+// Drops already-explained (reflected) names so they do not keep the batch
+// "dirty", then isolates the culprit or splits the rest in two
+fn narrow(batch: Vec<String>, explained: &[&str]) -> Narrowed {
+    let mut remaining: Vec<String> = batch.into_iter().filter(|n| !explained.contains(&n.as_str())).collect();
+    match remaining.len() {
+        0 => Narrowed::Explained,
+        1 => Narrowed::Found(remaining.remove(0)),
+        n => {
+            let second = remaining.split_off(n / 2);
+            Narrowed::Halves(remaining, second)
+        }
+    }
+}
+
+// Fixed request context shared by every batch
+struct BatchSender<'a> {
+    client: &'a Client,
+    base_url: &'a str,
+    base_data: &'a Option<String>,
+    method: &'a str,
+    headers: &'a [(String, String)],
+    location: ParamLocation,
+    delay: Duration,
+    max_body: usize,
+    sent: Cell<usize>,  // Requests attempted, failed ones included
+}
+
+impl BatchSender<'_> {
+    // Sends one batch, sleeping afterwards to respect the request rate
+    async fn send(&self, batch: &[(String, String)]) -> Option<ProbeResult> {
+        let (url, data) = build_request(self.base_url, self.base_data, self.location, batch);
+        self.sent.set(self.sent.get() + 1);
+        let res = probe_url(url.clone(), self.client, self.method, data, self.headers.to_vec(), self.max_body).await;
+        tokio::time::sleep(self.delay).await;
+        match res {
+            Ok(r) => Some(r),
+            Err(e) => {
+                eprintln!("Probe error for {}: {}", url, e);
+                None
+            }
+        }
+    }
+}
+
+// Everything a discovery run is configured with, from the command line
+pub struct DiscoverOptions {
+    pub base_url: String,
+    pub wordlist_path: String,             // Candidate parameter names
+    pub location: ParamLocation,
+    pub batch_size: usize,                 // Candidates sent per request
+    pub rate: usize,                       // Requests per second
+    pub output: String,                    // Format, optionally ":file" (e.g. json:params.json)
+    pub method: String,
+    pub data: Option<String>,              // Body the candidates are added to
+    pub raw_headers: Vec<String>,          // "Name: value" strings from -H
+    pub proxy_url: Option<String>,
+    pub proxy_auth: Option<String>,
+    pub max_body: usize,
+}
+
+// Discovers hidden parameters Arjun-style: batches candidates per request,
+// compares against a baseline and binary-splits batches that cause a change
+pub async fn discover(options: DiscoverOptions) {
+    let DiscoverOptions {
+        base_url,
+        wordlist_path,
+        location,
+        batch_size,
+        rate,
+        output,
+        method,
+        data,
+        raw_headers,
+        proxy_url,
+        proxy_auth,
+        max_body,
+    } = options;
+
+    let names = match load_wordlist(&wordlist_path) {
+        Ok(w) => w.into_iter().map(|w| w.trim().to_string()).filter(|w| !w.is_empty()).collect::<Vec<_>>(),
+        Err(e) => {
+            eprintln!("Error loading wordlist: {}", e);
+            return;
+        }
+    };
+
+    let parsed_headers = with_content_type(parse_headers(raw_headers), location);
+    let client = build_client(proxy_url, proxy_auth);
+    let delay = Duration::from_secs_f64(1.0 / rate.max(1) as f64);
+
+    // Body locations need a method that carries a body
+    let mut method_upper = method.to_uppercase();
+    if location != ParamLocation::Query && !matches!(method_upper.as_str(), "POST" | "PUT") {
+        method_upper = "POST".to_string();
+    }
+
+    let sender = BatchSender {
+        client: &client,
+        base_url: &base_url,
+        base_data: &data,
+        method: &method_upper,
+        headers: &parsed_headers,
+        location,
+        delay,
+        max_body,
+        sent: Cell::new(0),
+    };
+
+    // Two identical baseline requests measure how much the page jitters on its own
+    let first = sender.send(&[]).await;
+    let second = sender.send(&[]).await;
+    let (first, second) = match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        _ => {
+            eprintln!("Could not establish a baseline for {}. Aborting.", base_url);
+            return;
+        }
+    };
+    if first.status != second.status {
+        eprintln!("Warning: baseline status is unstable ({} vs {}). Results may be noisy.", first.status, second.status);
+    }
+    let baseline = Baseline {
+        status: first.status,
+        body_len: first.body_len,
+        len_tolerance: first.body_len.abs_diff(second.body_len),
+        title: first.title.clone(),
+    };
+
+    println!(
+        "Baseline: status {}, length {} (±{}). Testing {} candidate(s) in batches of {}.",
+        baseline.status, baseline.body_len, baseline.len_tolerance, names.len(), batch_size
+    );
+
+    // Work stack of batches still to test; changed batches are split in half until isolated
+    let mut stack: Vec<Vec<String>> = names.chunks(batch_size.max(1)).map(|c| c.to_vec()).collect();
+    stack.reverse();
+    let mut found: Vec<DiscoveredParam> = vec![];
+
+    while let Some(batch) = stack.pop() {
+        let pairs: Vec<(String, String)> = batch.iter().map(|n| (n.clone(), random_token())).collect();
+        let result = match sender.send(&pairs).await {
+            Some(r) => r,
+            None => continue,
+        };
+
+        // Reflected values identify their parameter directly, no splitting needed
+        let reflected_names = reflected(&result, &pairs);
+        for name in &reflected_names {
+            if !found.iter().any(|f| f.name == *name) {
+                found.push(DiscoveredParam {
+                    name: name.to_string(),
+                    location,
+                    reason: "value reflected in response".to_string(),
+                });
+            }
+        }
+
+        if let Some(reason) = compare(&baseline, &result) {
+            match narrow(batch, &reflected_names) {
+                Narrowed::Found(name) => found.push(DiscoveredParam { name, location, reason }),
+                Narrowed::Halves(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Narrowed::Explained => {}
+            }
+        }
+    }
+
+    println!("Sent {} request(s), found {} parameter(s).", sender.sent.get(), found.len());
+
+    // Parse output arg (e.g., "json:params.json")
+    let parts: Vec<&str> = output.splitn(2, ':').collect();
+    let out_format = parts[0].to_lowercase();
+    let out_path = parts.get(1).map(|&s| s.to_string());
+
+    let content = match out_format.as_str() {
+        "json" => serde_json::to_string(&found).unwrap(),
+        "pretty-json" => serde_json::to_string_pretty(&found).unwrap(),
+        _ => found
+            .iter()
+            .map(|p| format!("Param: {} ({:?})\nReason: {}\n---\n", p.name, p.location, p.reason))
+            .collect(),
+    };
+
+    match out_path {
+        Some(mut path) => {
+            if path.is_empty() {
+                path = format!("grimnir_params_{}.txt", Local::now().format("%Y%m%d_%H%M%S"));
+            }
+            match File::create(&path).and_then(|mut f| f.write_all(content.as_bytes())) {
+                Ok(_) => println!("Output saved to '{}'", path),
+                Err(e) => eprintln!("Failed to write to '{}': {}", path, e),
+            }
+        }
+        None => print!("{}", content),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn query_names_are_percent_encoded() {
+        let batch = pairs(&[("a&b=c", "1"), ("x y#z+", "2"), ("név", "3")]);
+        let (url, _) = build_request("http://t/s?keep=1", &None, ParamLocation::Query, &batch);
+        assert_eq!(url, "http://t/s?keep=1&a%26b%3Dc=1&x+y%23z%2B=2&n%C3%A9v=3");
+        let parsed = Url::parse(&url).unwrap();
+        let names: Vec<String> = parsed.query_pairs().map(|(k, _)| k.into_owned()).collect();
+        assert_eq!(names, ["keep", "a&b=c", "x y#z+", "név"]);
+    }
+
+    #[test]
+    fn empty_batch_leaves_the_url_alone() {
+        let (url, data) = build_request("http://t/s?q=1", &Some("d=1".to_string()), ParamLocation::Query, &[]);
+        assert_eq!(url, "http://t/s?q=1");
+        assert_eq!(data.as_deref(), Some("d=1"));
+    }
+
+    #[test]
+    fn form_pairs_are_encoded_after_user_data() {
+        let batch = pairs(&[("a&b", "v=1"), ("c", "2")]);
+        let (url, data) = build_request("http://t/login", &Some("user=x".to_string()), ParamLocation::Form, &batch);
+        assert_eq!(url, "http://t/login");
+        assert_eq!(data.as_deref(), Some("user=x&a%26b=v%3D1&c=2"));
+    }
+
+    #[test]
+    fn form_baseline_without_data_is_form_encoded() {
+        let (_, baseline) = build_request("http://t/login", &None, ParamLocation::Form, &[]);
+        let (_, batch) = build_request("http://t/login", &None, ParamLocation::Form, &pairs(&[("a", "1")]));
+        assert_eq!((baseline.as_deref(), batch.as_deref()), (Some(""), Some("a=1")));
+        let headers = with_content_type(vec![], ParamLocation::Form);
+        assert_eq!(headers, pairs(&[("Content-Type", "application/x-www-form-urlencoded")]));
+        let custom = pairs(&[("content-type", "application/x-www-form-urlencoded; charset=utf-8")]);
+        assert_eq!(with_content_type(custom.clone(), ParamLocation::Form), custom);
+        assert!(with_content_type(vec![], ParamLocation::Query).is_empty());
+    }
+
+    #[test]
+    fn json_merges_into_user_object() {
+        let batch = pairs(&[("debug", "t")]);
+        let (_, data) = build_request("http://t/api", &Some(r#"{"id":1}"#.to_string()), ParamLocation::Json, &batch);
+        let value: serde_json::Value = serde_json::from_str(&data.unwrap()).unwrap();
+        assert_eq!(value, serde_json::json!({"id": 1, "debug": "t"}));
+    }
+
+    #[test]
+    fn narrowing_isolates_or_splits() {
+        let names = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(narrow(names(&["a"]), &[]), Narrowed::Found("a".to_string()));
+        assert_eq!(narrow(names(&["a", "b"]), &["a"]), Narrowed::Found("b".to_string()));
+        assert_eq!(narrow(names(&["a"]), &["a"]), Narrowed::Explained);
+        assert_eq!(
+            narrow(names(&["a", "b", "c", "d", "e"]), &[]),
+            Narrowed::Halves(names(&["a", "b"]), names(&["c", "d", "e"]))
+        );
+    }
+}
@@ -1,16 +1,152 @@
+use crate::clustering::{ClusterInfo, PageSignature};
+use crate::favicon::FaviconHash;
+use crate::headers::{parse_set_cookie, Cookie, Headers};
+use crate::html_parser::{parse_html, HtmlInfo};
+use crate::js_assets::ScriptAsset;
+use crate::js_extract::JsFindings;
+use crate::waf_detector::WafDetection;
 use reqwest::{Client, Error as ReqwestError};
-use regex::Regex;
 use serde::Serialize;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+// Default cap on how much of a response body is read into memory
+pub const DEFAULT_MAX_BODY: usize = 1024 * 1024;
 
 // Custom struct for probe results—easy to expand and serialize
 #[derive(Debug, Serialize)]
 pub struct ProbeResult {
     pub url: String,
     pub status: u16,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,              // Ordered, repeated headers kept
+    pub cookies: Vec<Cookie>,          // Parsed from every Set-Cookie header
     pub title: Option<String>,
-    pub body_snippet: Option<String>,  // First 1KB of body for fingerprinting/AI
+    pub html: Option<HtmlInfo>,        // Parsed document for HTML responses
+    pub body_len: usize,               // Bytes actually read (capped at max_body)
+    pub body_truncated: bool,          // True if the body exceeded max_body
+    pub body_sha256: Option<String>,   // Hash of the bytes read, also the storage key
+    pub body_snippet: Option<String>,  // First 1KB of body for display/AI
+    pub elapsed_ms: u64,               // From sending the request to the last body byte read
+    pub stored_path: Option<String>,   // Set when the full response was written to disk
+    pub favicon: Option<FaviconHash>,  // Host favicon hashes, set when --favicon is enabled
+    pub waf: Option<WafDetection>,     // WAF/CDN seen in this response, set when --waf is enabled
+    pub cluster: Option<ClusterInfo>,  // Similar-response group, set after the scan when --cluster is enabled
+    pub js: Option<JsFindings>,        // Endpoints, GraphQL operations and keys in its JavaScript (--js-extract)
+    #[serde(skip)]
+    pub body: Vec<u8>,                 // Raw bytes, binary-safe; use body_text() for matching. Empty once released
+    #[serde(skip)]
+    pub binary: bool,                  // See is_binary(); kept when the body is released
+    #[serde(skip)]
+    pub words: usize,                  // Whitespace-separated words in the body (0 when binary)
+    #[serde(skip)]
+    pub signature: Option<PageSignature>,  // Similarity features, taken before the body is released (--cluster)
+    #[serde(skip)]
+    pub scripts: Vec<Arc<ScriptAsset>>,  // Same-origin JS fetched for fingerprinting or extraction (--fetch-js, --js-extract)
+    #[serde(skip)]
+    pub tech: Vec<String>,               // Detected tech labels, for analyzers (--tech)
+}
+
+impl ProbeResult {
+    // Builds a result from a received response: cookies, hash, snippet and
+    // HTML parsing are derived here, so requests and tests share one path
+    pub fn from_response(
+        url: String,
+        status: u16,
+        headers: Headers,
+        body: Vec<u8>,
+        body_truncated: bool,
+        elapsed_ms: u64,
+    ) -> ProbeResult {
+        let cookies: Vec<Cookie> = headers.get_all("set-cookie").filter_map(|c| parse_set_cookie(c)).collect();
+        let body_sha256 = if !body.is_empty() { Some(format!("{:x}", Sha256::digest(&body))) } else { None };
+        let text = String::from_utf8_lossy(&body);
+        let body_snippet = if !text.is_empty() { Some(text.chars().take(1024).collect()) } else { None };
+
+        // Parse HTML documents (by content type, or by sniffing when it is missing)
+        let looks_html = headers
+            .get("content-type")
+            .map_or_else(|| text.trim_start().starts_with('<'), |ct| ct.to_lowercase().contains("html"));
+        let html = if looks_html && !text.is_empty() { Some(parse_html(&text)) } else { None };
+        let title = html.as_ref().and_then(|h| h.title.clone());
+        let binary = looks_binary(&headers, &body);
+        let words = if binary { 0 } else { text.split_whitespace().count() };
+
+        ProbeResult {
+            url,
+            status,
+            headers,
+            cookies,
+            title,
+            html,
+            body_len: body.len(),
+            body_truncated,
+            body_sha256,
+            body_snippet,
+            elapsed_ms,
+            stored_path: None,
+            favicon: None,
+            waf: None,
+            cluster: None,
+            js: None,
+            body,
+            binary,
+            words,
+            signature: None,
+            scripts: vec![],
+            tech: vec![],
+        }
+    }
+
+    // Lossy UTF-8 view of the full captured body (fingerprinting, filters)
+    pub fn body_text(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.body)
+    }
+
+    // Heuristic: NUL bytes or a non-text content type mean binary content
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    // Frees the body (and inline scripts) once per-result work is done, so a
+    // long scan keeps only metadata for the results it has kept. The post-scan
+    // passes use the hash, counts and similarity signature instead; the full
+    // response stays available in the --store-responses file.
+    pub fn release_body(&mut self) {
+        self.body = Vec::new();
+        if let Some(html) = &mut self.html {
+            html.inline_scripts = Vec::new();
+        }
+    }
+}
+
+fn looks_binary(headers: &Headers, body: &[u8]) -> bool {
+    let text_type = headers.get("content-type").is_none_or(|ct| {
+        let ct = ct.to_lowercase();
+        ct.starts_with("text/") || ct.contains("json") || ct.contains("xml") || ct.contains("javascript")
+    });
+    !text_type || body.iter().take(512).any(|&b| b == 0)
+}
+
+// Writes the full response (status, headers, raw body) to dir/<sha256>.http.
+// Identical bodies share one file, so repeated error pages are stored once.
+pub fn store_response(dir: &Path, result: &ProbeResult) -> io::Result<PathBuf> {
+    let hash = result.body_sha256.clone().unwrap_or_else(|| "empty".to_string());
+    let path = dir.join(format!("{}.http", hash));
+    if !path.exists() {
+        let mut content = format!("{}\nStatus: {}\n", result.url, result.status).into_bytes();
+        for (key, value) in result.headers.iter() {
+            content.extend_from_slice(format!("{}: {}\n", key, value).as_bytes());
+        }
+        content.push(b'\n');
+        content.extend_from_slice(&result.body);
+        fs::write(&path, content)?;
+    }
+    Ok(path)
 }
 
 // Async function to probe a URL and parse response
@@ -20,6 +156,7 @@ pub async fn probe_url(
     method: &str,
     data: Option<String>,
     custom_headers: Vec<(String, String)>,  // Custom headers to attach
+    max_body: usize,                        // Stop reading the body after this many bytes
 ) -> Result<ProbeResult, ReqwestError> {
     let mut request = match method {
         "GET" => client.get(&url),
@@ -30,49 +167,174 @@ pub async fn probe_url(
     };
 
     // Attach custom headers first (allows overriding defaults)
+    let custom_type = custom_headers.iter().any(|(key, _)| key.eq_ignore_ascii_case("content-type"));
     for (key, value) in custom_headers {
         request = request.header(key, value);
     }
 
-    // Attach data for methods that support bodies (POST, PUT)
+    // Attach data for methods that support bodies (POST, PUT); the content
+    // type is guessed from the body unless one was passed in the headers
     if matches!(method, "POST" | "PUT") {
         if let Some(body) = data {
-            let content_type = if body.starts_with('{') || body.starts_with('[') {
-                "application/json"
-            } else if body.contains('=') {
-                "application/x-www-form-urlencoded"
-            } else {
-                "text/plain"
-            };
-            request = request.header("Content-Type", content_type).body(body);
+            if !custom_type {
+                let content_type = if body.starts_with('{') || body.starts_with('[') {
+                    "application/json"
+                } else if body.contains('=') {
+                    "application/x-www-form-urlencoded"
+                } else {
+                    "text/plain"
+                };
+                request = request.header("Content-Type", content_type);
+            }
+            request = request.body(body);
         }
     }
 
-    let res = request.send().await?;
+    let started = Instant::now();
+    let mut res = request.send().await?;
 
     let status = res.status().as_u16();
 
-    // Collect headers into a HashMap (lowercase keys for consistency)
-    let mut headers = HashMap::new();
+    // Collect headers in order, keeping every value of repeated headers
+    let mut headers = Headers::default();
     for (key, value) in res.headers() {
-        if let Ok(val_str) = value.to_str() {
-            headers.insert(key.as_str().to_lowercase(), val_str.to_string());
+        headers.push(key.as_str().to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned());
+    }
+
+    // Stream the body up to max_body (HEAD has no body)
+    let mut body: Vec<u8> = Vec::new();
+    let mut body_truncated = false;
+    if method != "HEAD" {
+        while let Some(chunk) = res.chunk().await? {
+            let room = max_body.saturating_sub(body.len());
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                body_truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+    }
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    Ok(ProbeResult::from_response(url, status, headers, body, body_truncated, elapsed_ms))
+}
+
+// Local HTTP stub for request tests: `respond` gets each request's path and
+// body and returns (status, content type, body). Returns the base URL and
+// how many requests were served.
+#[cfg(test)]
+pub(crate) async fn test_server<F>(respond: F) -> (String, Arc<std::sync::atomic::AtomicUsize>)
+where
+    F: Fn(&str, &str) -> (u16, String, Vec<u8>) + Send + Sync + 'static,
+{
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let served = Arc::new(AtomicUsize::new(0));
+    let (respond, counter) = (Arc::new(respond), served.clone());
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let (respond, counter) = (respond.clone(), counter.clone());
+            tokio::spawn(async move {
+                // Head first, then as much body as Content-Length announces
+                let mut request = Vec::new();
+                let mut buf = [0u8; 8192];
+                let mut body_start = None;
+                let mut length = 0;
+                while body_start.is_none_or(|start| request.len() < start + length) {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                    if body_start.is_none() {
+                        body_start = request.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4);
+                        let head = String::from_utf8_lossy(&request[..body_start.unwrap_or(0)]).to_lowercase();
+                        length = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:").and_then(|v| v.trim().parse().ok()))
+                            .unwrap_or(0);
+                    }
+                }
+                let start = body_start.unwrap();
+                let head = String::from_utf8_lossy(&request[..start]).into_owned();
+                let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let (status, content_type, body) = respond(&path, &String::from_utf8_lossy(&request[start..]));
+                let head = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    content_type,
+                    body.len()
+                );
+                let _ = stream.write_all(head.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+                let _ = stream.shutdown().await;
+            });
         }
+    });
+    (base_url, served)
+}
+
+// Result for a canned response, as a test would receive it from probe_url
+#[cfg(test)]
+pub(crate) fn test_response(url: &str, status: u16, headers: &[(&str, &str)], body: impl AsRef<[u8]>) -> ProbeResult {
+    let mut parsed = Headers::default();
+    for (name, value) in headers {
+        parsed.push(name.to_string(), value.to_string());
+    }
+    ProbeResult::from_response(url.to_string(), status, parsed, body.as_ref().to_vec(), false, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(url: &str, content_type: Option<&str>, body: &[u8]) -> ProbeResult {
+        let headers: Vec<(&str, &str)> = content_type.map(|ct| ("content-type", ct)).into_iter().collect();
+        test_response(url, 200, &headers, body)
+    }
+
+    #[test]
+    fn derived_fields() {
+        let result = response("http://t/", None, b"  <html><title>Home</title><p>two words</p></html>");
+        assert_eq!(result.title.as_deref(), Some("Home"));
+        assert_eq!(result.body_len, 50);
+        assert_eq!(result.body_sha256.as_ref().map(String::len), Some(64));
+        assert!(!result.is_binary());
+        let empty = response("http://t/", Some("text/html"), b"");
+        assert!(empty.body_sha256.is_none() && empty.body_snippet.is_none() && empty.html.is_none());
     }
 
-    // Extract body only if not HEAD (HEAD has no body)
-    let body = if method != "HEAD" { res.text().await? } else { String::new() };
-    let body_snippet = if !body.is_empty() { Some(body.chars().take(1024).collect()) } else { None };
-
-    // Parse title with regex (assumes HTML)
-    let title_re = Regex::new(r"<title>(.*?)</title>").unwrap();
-    let title = title_re.captures(&body).and_then(|caps| caps.get(1).map(|m| m.as_str().to_string()));
-
-    Ok(ProbeResult {
-        url,
-        status,
-        headers,
-        title,
-        body_snippet,
-    })
+    #[test]
+    fn binary_bodies() {
+        assert!(response("http://t/a.png", Some("image/png"), b"PNG").is_binary());
+        assert!(response("http://t/a", None, b"abc\0def").is_binary());
+        let json = response("http://t/api", Some("application/json; charset=utf-8"), br#"{"a": 1}"#);
+        assert!(!json.is_binary());
+        assert_eq!(json.words, 2);
+    }
+
+    #[test]
+    fn release_body_keeps_metadata() {
+        let mut result = response("http://t/", Some("text/html"), b"<script>var a = 1;</script>");
+        result.release_body();
+        assert!(result.body.is_empty() && result.html.as_ref().unwrap().inline_scripts.is_empty());
+        assert_eq!(result.body_len, 27);
+        assert!(result.body_sha256.is_some());
+    }
+
+    #[test]
+    fn identical_bodies_are_stored_once() {
+        let dir = std::env::temp_dir().join(format!("grimnir-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = store_response(&dir, &response("http://t/a", Some("text/plain"), b"not found")).unwrap();
+        let second = store_response(&dir, &response("http://t/b", Some("text/plain"), b"not found")).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "http://t/a\nStatus: 200\ncontent-type: text/plain\n\nnot found");
+        let empty = store_response(&dir, &response("http://t/c", None, b"")).unwrap();
+        assert!(empty.ends_with("empty.http"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,358 @@
+use crate::ai_engine::Analysis;
+use crate::prober::ProbeResult;
+use crate::signatures::one_or_many;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex, RegexBuilder};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// Score rules bundled with grimnir; they reproduce the built-in heuristics
+const BUNDLED_RULES: &str = include_str!("../data/score_rules.json");
+
+// Result fields a condition can test; "header.<name>" tests any response header
+const FIELDS: [&str; 12] = [
+    "status", "length", "words", "url", "path", "title", "body", "content_type", "cookies", "tech", "password_form", "waf",
+];
+
+// Analyzers that apply score rules
+const ANALYZERS: [&str; 2] = ["heuristics", "sentiment"];
+
+// Fields only the sentiment analyzer provides (negative-polarity confidence, 0 when positive)
+const SENTIMENT_FIELDS: [&str; 2] = ["sentiment.title_negative", "sentiment.body_negative"];
+
+lazy_static! {
+    // "{field}" placeholders in insights
+    static ref PLACEHOLDER_RE: Regex = Regex::new(r"\{([a-z0-9_.\-]+)\}").unwrap();
+}
+
+fn default_analyzer() -> String {
+    "heuristics".to_string()
+}
+
+// One test of one field, as written in a rules file. Every operator given
+// must hold; text comparisons ignore case, and a list field matches if any
+// of its items does.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConditionDef {
+    field: String,
+    equals: Option<Value>,
+    #[serde(default, rename = "in")]
+    one_of: Option<Vec<Value>>,
+    #[serde(default, deserialize_with = "some_one_or_many")]
+    contains: Option<Vec<String>>,  // Any of these substrings
+    matches: Option<String>,        // Regex
+    gt: Option<f64>,
+    lt: Option<f64>,
+    exists: Option<bool>,
+}
+
+fn some_one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<String>>, D::Error> {
+    one_or_many(deserializer).map(Some)
+}
+
+// One rule as written in a rules file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleDef {
+    id: String,
+    #[serde(default = "default_analyzer")]
+    analyzer: String,  // Which analyzer applies the rule (heuristics or sentiment)
+    weight: f32,       // Added to the score when the rule matches; 0 disables it
+    insight: String,   // May quote fields: "Interesting server: {header.server}"
+    #[serde(default)]
+    all: Vec<ConditionDef>,
+    #[serde(default)]
+    any: Vec<ConditionDef>,
+    #[serde(default)]
+    none: Vec<ConditionDef>,
+}
+
+struct Condition {
+    field: String,
+    equals: Option<Value>,
+    one_of: Option<Vec<Value>>,
+    contains: Option<Vec<String>>,  // Lowercased
+    matches: Option<Regex>,
+    gt: Option<f64>,
+    lt: Option<f64>,
+    exists: Option<bool>,
+}
+
+// A rule with its conditions compiled
+struct ScoreRule {
+    id: String,
+    analyzer: String,
+    weight: f32,
+    insight: String,
+    all: Vec<Condition>,
+    any: Vec<Condition>,
+    none: Vec<Condition>,
+}
+
+fn known_field(field: &str) -> bool {
+    FIELDS.contains(&field) || SENTIMENT_FIELDS.contains(&field) || field.strip_prefix("header.").is_some_and(|h| !h.is_empty())
+}
+
+fn compile_condition(source: &str, id: &str, def: ConditionDef) -> Result<Condition, String> {
+    if !known_field(&def.field) {
+        return Err(format!(
+            "Unknown field '{}' in score rule '{}' in '{}'. Available: {}, header.<name>, {}",
+            def.field,
+            id,
+            source,
+            FIELDS.join(", "),
+            SENTIMENT_FIELDS.join(", ")
+        ));
+    }
+    let no_operator = def.equals.is_none()
+        && def.one_of.is_none()
+        && def.contains.is_none()
+        && def.matches.is_none()
+        && def.gt.is_none()
+        && def.lt.is_none()
+        && def.exists.is_none();
+    if no_operator {
+        return Err(format!("Condition on '{}' in score rule '{}' in '{}' has no operator", def.field, id, source));
+    }
+    let matches = match &def.matches {
+        Some(pattern) => Some(
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| format!("Invalid pattern in score rule '{}' in '{}': {}", id, source, e))?,
+        ),
+        None => None,
+    };
+    Ok(Condition {
+        field: def.field,
+        equals: def.equals,
+        one_of: def.one_of,
+        contains: def.contains.map(|c| c.iter().map(|s| s.to_lowercase()).collect()),
+        matches,
+        gt: def.gt,
+        lt: def.lt,
+        exists: def.exists,
+    })
+}
+
+fn parse_rules(source: &str, content: &str) -> Result<Vec<ScoreRule>, String> {
+    let defs: Vec<RuleDef> =
+        serde_json::from_str(content).map_err(|e| format!("Invalid score rules in '{}': {}", source, e))?;
+    defs.into_iter()
+        .map(|def| {
+            if !ANALYZERS.contains(&def.analyzer.as_str()) {
+                return Err(format!(
+                    "Unknown analyzer '{}' in score rule '{}' in '{}'. Available: {}",
+                    def.analyzer,
+                    def.id,
+                    source,
+                    ANALYZERS.join(", ")
+                ));
+            }
+            let compile = |conditions: Vec<ConditionDef>| -> Result<Vec<Condition>, String> {
+                conditions.into_iter().map(|c| compile_condition(source, &def.id, c)).collect()
+            };
+            Ok(ScoreRule {
+                all: compile(def.all)?,
+                any: compile(def.any)?,
+                none: compile(def.none)?,
+                id: def.id,
+                analyzer: def.analyzer,
+                weight: def.weight,
+                insight: def.insight,
+            })
+        })
+        .collect()
+}
+
+// Text and numbers compare loosely: "200" equals 200, "NGINX" equals "nginx"
+fn value_eq(actual: &Value, expected: &Value) -> bool {
+    match (actual, expected) {
+        (Value::String(a), Value::String(e)) => a.eq_ignore_ascii_case(e),
+        (a, e) => match (as_number(a), as_number(e)) {
+            (Some(a), Some(e)) => a == e,
+            _ => a == e,
+        },
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if f.fract() != 0.0 => format!("{:.2}", f),
+            _ => n.to_string(),
+        },
+        Value::Array(items) => items.iter().map(as_text).collect::<Vec<_>>().join(", "),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+impl Condition {
+    fn holds(&self, value: &Value) -> bool {
+        let present = !value.is_null();
+        if self.exists.is_some_and(|e| e != present) {
+            return false;
+        }
+        let tests_value = self.equals.is_some()
+            || self.one_of.is_some()
+            || self.contains.is_some()
+            || self.matches.is_some()
+            || self.gt.is_some()
+            || self.lt.is_some();
+        if !tests_value {
+            return true;
+        }
+        match value {
+            Value::Null => false,
+            Value::Array(items) => items.iter().any(|item| self.holds_one(item)),
+            single => self.holds_one(single),
+        }
+    }
+
+    fn holds_one(&self, value: &Value) -> bool {
+        let text = as_text(value);
+        let number = as_number(value);
+        self.equals.as_ref().is_none_or(|e| value_eq(value, e))
+            && self.one_of.as_ref().is_none_or(|list| list.iter().any(|e| value_eq(value, e)))
+            && self.contains.as_ref().is_none_or(|subs| {
+                let lower = text.to_lowercase();
+                subs.iter().any(|s| lower.contains(s.as_str()))
+            })
+            && self.matches.as_ref().is_none_or(|re| re.is_match(&text))
+            && self.gt.is_none_or(|g| number.is_some_and(|n| n > g))
+            && self.lt.is_none_or(|l| number.is_some_and(|n| n < l))
+    }
+}
+
+// A field of the result, or of the analyzer's own findings (extra)
+fn field_value(field: &str, result: &ProbeResult, extra: &[(&str, Value)]) -> Value {
+    if let Some((_, value)) = extra.iter().find(|(name, _)| *name == field) {
+        return value.clone();
+    }
+    if let Some(name) = field.strip_prefix("header.") {
+        return result.headers.get(name).map_or(Value::Null, |v| Value::from(v.as_str()));
+    }
+    let text = |s: &Option<String>| s.as_ref().map_or(Value::Null, |s| Value::from(s.as_str()));
+    match field {
+        "status" => Value::from(result.status),
+        "length" => Value::from(result.body_len),
+        "words" => Value::from(result.words),
+        "url" => Value::from(result.url.as_str()),
+        "path" => reqwest::Url::parse(&result.url).map_or(Value::Null, |u| Value::from(u.path())),
+        "title" => text(&result.title),
+        "body" if result.is_binary() => Value::Null,
+        "body" => Value::from(result.body_text().into_owned()),
+        "content_type" => text(&result.headers.get("content-type").cloned()),
+        "cookies" => Value::from(result.cookies.iter().map(|c| c.name.clone()).collect::<Vec<_>>()),
+        "tech" => Value::from(result.tech.clone()),
+        "password_form" => Value::from(result.html.as_ref().is_some_and(|h| h.has_password_form())),
+        "waf" => result.waf.as_ref().map_or(Value::Null, |w| Value::from(w.vendor.as_str())),
+        _ => Value::Null,
+    }
+}
+
+// Weighted conditions over result fields, each adding its weight to the score
+// when it matches. The rule ID names the score component, so every result
+// carries a breakdown of which rules contributed what.
+pub struct ScoreRules {
+    rules: Vec<ScoreRule>,
+}
+
+impl ScoreRules {
+    // Bundled rules, plus a custom rules file in the same format; only the
+    // rules for the given analyzer are kept
+    pub fn load(custom_file: Option<&Path>, analyzer: &str) -> Result<ScoreRules, String> {
+        let mut rules = parse_rules("bundled", BUNDLED_RULES).expect("Invalid bundled score rules");
+        if let Some(path) = custom_file {
+            let content = fs::read_to_string(path).map_err(|e| format!("Cannot read score rules '{}': {}", path.display(), e))?;
+            for rule in parse_rules(&path.display().to_string(), &content)? {
+                // A custom rule replaces the bundled one with the same ID, in place
+                match rules.iter().position(|r| r.id == rule.id) {
+                    Some(i) => rules[i] = rule,
+                    None => rules.push(rule),
+                }
+            }
+        }
+        rules.retain(|r| r.analyzer == analyzer && r.weight != 0.0);
+        Ok(ScoreRules { rules })
+    }
+
+    // Adds a component (named after the rule) and an insight for every matching rule
+    pub fn apply(&self, analyzer: &str, result: &ProbeResult, extra: &[(&str, Value)], analysis: &mut Analysis) {
+        let value = |field: &str| field_value(field, result, extra);
+        for rule in &self.rules {
+            let matched = rule.all.iter().all(|c| c.holds(&value(&c.field)))
+                && (rule.any.is_empty() || rule.any.iter().any(|c| c.holds(&value(&c.field))))
+                && !rule.none.iter().any(|c| c.holds(&value(&c.field)));
+            if matched {
+                let insight = PLACEHOLDER_RE.replace_all(&rule.insight, |caps: &Captures| as_text(&value(&caps[1])));
+                analysis.add(analyzer, &rule.id, rule.weight, insight.into_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn condition(def: Value) -> Condition {
+        compile_condition("test", "rule", serde_json::from_value(def).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn bundled_rules_parse() {
+        let rules = parse_rules("bundled", BUNDLED_RULES).unwrap();
+        assert!(rules.iter().any(|r| r.analyzer == "heuristics"));
+    }
+
+    #[test]
+    fn text_operators_ignore_case() {
+        let matches = condition(json!({"field": "title", "matches": "^admin (panel|login)$"}));
+        assert!(matches.holds(&json!("Admin Login")));
+        assert!(!matches.holds(&json!("Administrator")));
+        let contains = condition(json!({"field": "header.server", "contains": ["NGINX", "apache"]}));
+        assert!(contains.holds(&json!("Apache/2.4.62 (Debian)")));
+        assert!(condition(json!({"field": "waf", "equals": "cloudflare"})).holds(&json!("Cloudflare")));
+    }
+
+    #[test]
+    fn numbers_lists_and_presence() {
+        let status = condition(json!({"field": "status", "in": [200, "204"]}));
+        assert!(status.holds(&json!(204)) && !status.holds(&json!(404)));
+        let length = condition(json!({"field": "length", "gt": 100, "lt": 1000}));
+        assert!(length.holds(&json!(500)) && !length.holds(&json!(1000)));
+        let cookies = condition(json!({"field": "cookies", "equals": "laravel_session"}));
+        assert!(cookies.holds(&json!(["XSRF-TOKEN", "laravel_session"])));
+        assert!(!cookies.holds(&Value::Null));
+        let missing = condition(json!({"field": "header.x-powered-by", "exists": false}));
+        assert!(missing.holds(&Value::Null) && !missing.holds(&json!("PHP/8.2")));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        let rule = |analyzer: &str, field: &str| {
+            format!(r#"[{{"id": "r", "analyzer": "{}", "weight": 0.1, "insight": "x", "all": [{{"field": "{}", "exists": true}}]}}]"#, analyzer, field)
+        };
+        assert!(parse_rules("test", &rule("sentiment", "status")).is_ok());
+        let err = parse_rules("test", &rule("heuristic", "status")).err().unwrap();
+        assert!(err.contains("Unknown analyzer 'heuristic'"), "{}", err);
+        assert!(parse_rules("test", &rule("heuristics", "server")).is_err());
+        assert!(parse_rules("test", r#"[{"id": "r", "weight": 0.1, "insight": "x", "all": [{"field": "status"}]}]"#).is_err());
+        assert!(parse_rules("test", r#"[{"id": "r", "weight": 0.1, "insight": "x", "all": [{"field": "body", "matches": "("}]}]"#).is_err());
+    }
+}
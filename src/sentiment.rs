@@ -0,0 +1,246 @@
+use crate::ai_engine::{Analysis, Analyzer, AnalyzerFuture};
+use crate::models;
+use crate::prober::ProbeResult;
+use crate::score_rules::ScoreRules;
+use rust_bert::pipelines::common::{ModelResource, ModelType};
+use rust_bert::pipelines::sentiment::{Sentiment, SentimentConfig, SentimentModel, SentimentPolarity};
+use rust_bert::resources::LocalResource;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+// Most texts handed to the model in one predict call
+const MAX_BATCH: usize = 32;
+
+// How long the worker waits for more requests before running a partial batch
+const BATCH_WINDOW: Duration = Duration::from_millis(10);
+
+// Points every resource of the default DistilBERT SST-2 pipeline at local files
+fn local_config(dir: &Path) -> SentimentConfig {
+    let local = |name: &str| Box::new(LocalResource::from(dir.join(name)));
+    SentimentConfig {
+        model_type: ModelType::DistilBert,
+        model_resource: ModelResource::Torch(local("rust_model.ot")),
+        config_resource: local("config.json"),
+        vocab_resource: local("vocab.txt"),
+        ..Default::default()
+    }
+}
+
+// Loads the model from a local directory, or from rust-bert's download cache
+// when none is configured. Missing local files disable sentiment rather than
+// silently falling back to the network.
+fn load_model(model_dir: Option<&Path>) -> Option<SentimentModel> {
+    let config = match model_dir {
+        Some(dir) => {
+            let missing = models::missing_files(dir);
+            if !missing.is_empty() {
+                eprintln!(
+                    "Sentiment model directory '{}' is missing {}. Run 'grimnir models fetch --model-dir {}' to prepare it. AI sentiment disabled.",
+                    dir.display(),
+                    missing.join(", "),
+                    dir.display()
+                );
+                return None;
+            }
+            local_config(dir)
+        }
+        None => SentimentConfig::default(),
+    };
+    match SentimentModel::new(config) {
+        Ok(model) => Some(model),
+        Err(e) => {
+            eprintln!("Failed to load sentiment model: {}. AI sentiment disabled.", e);
+            None
+        }
+    }
+}
+
+// Texts from one result and where to send their predictions (same order)
+struct Job<T> {
+    texts: Vec<String>,
+    reply: oneshot::Sender<Vec<T>>,
+}
+
+// Inference thread: owns the model and serves jobs until every sender is
+// gone; if the model fails to load, dropped replies disable sentiment.
+fn run_worker(model_dir: Option<PathBuf>, jobs: Receiver<Job<Sentiment>>) {
+    if let Some(model) = load_model(model_dir.as_deref()) {
+        serve(jobs, |inputs| model.predict(inputs));
+    }
+}
+
+// Batching loop, kept apart from the model: gathers pending jobs into
+// batches (up to MAX_BATCH texts or BATCH_WINDOW), calls predict once per
+// batch and answers each job with its slice of the predictions
+fn serve<T>(jobs: Receiver<Job<T>>, mut predict: impl FnMut(&[&str]) -> Vec<T>) {
+    while let Ok(first) = jobs.recv() {
+        let mut batch = vec![first];
+        let mut texts = batch[0].texts.len();
+        let deadline = Instant::now() + BATCH_WINDOW;
+        while texts < MAX_BATCH {
+            match jobs.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(job) => {
+                    texts += job.texts.len();
+                    batch.push(job);
+                }
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        let inputs: Vec<&str> = batch.iter().flat_map(|j| j.texts.iter().map(String::as_str)).collect();
+        let mut predictions = predict(&inputs).into_iter();
+        for job in batch {
+            let answer: Vec<T> = predictions.by_ref().take(job.texts.len()).collect();
+            let _ = job.reply.send(answer);  // The scan task may have gone away
+        }
+    }
+}
+
+// NLP sentiment on the title and body snippet; strongly negative text
+// usually means an error page, so the sentiment score rules lower the score.
+// Inference runs on a dedicated thread, so scan tasks only await the result.
+pub struct SentimentAnalyzer {
+    jobs: Sender<Job<Sentiment>>,
+    rules: ScoreRules,
+}
+
+impl SentimentAnalyzer {
+    pub fn new(model_dir: Option<&Path>, rules: ScoreRules) -> SentimentAnalyzer {
+        let (jobs, receiver) = mpsc::channel();
+        let model_dir = model_dir.map(Path::to_path_buf);
+        thread::Builder::new()
+            .name("grimnir-sentiment".to_string())
+            .spawn(move || run_worker(model_dir, receiver))
+            .expect("Failed to start sentiment worker");
+        SentimentAnalyzer { jobs, rules }
+    }
+
+    // Sentiments for the texts, or None when the model is unavailable
+    async fn predict(&self, texts: Vec<String>) -> Option<Vec<Sentiment>> {
+        let (reply, answer) = oneshot::channel();
+        self.jobs.send(Job { texts, reply }).ok()?;
+        answer.await.ok()
+    }
+}
+
+impl Analyzer for SentimentAnalyzer {
+    fn name(&self) -> &'static str {
+        "sentiment"
+    }
+
+    fn analyze<'a>(&'a self, result: &'a ProbeResult, analysis: &'a mut Analysis) -> AnalyzerFuture<'a> {
+        Box::pin(async move {
+            // Title (skip if empty) and body snippet (text bodies only) go in one request
+            let title = result.title.clone().filter(|t| !t.is_empty());
+            let body = result.body_snippet.clone().filter(|b| !b.is_empty() && !result.is_binary());
+            let texts: Vec<String> = title.iter().chain(body.iter()).cloned().collect();
+            if texts.is_empty() {
+                return;
+            }
+            let sentiments = match self.predict(texts).await {
+                Some(s) => s,
+                None => return,
+            };
+            // Confidence that each text is negative, for the sentiment.* rule fields
+            let negative = |s: &Sentiment| if s.polarity == SentimentPolarity::Negative { s.score } else { 0.0 };
+            let mut sentiments = sentiments.iter();
+            let mut fields: Vec<(&str, Value)> = vec![];
+            if title.is_some() {
+                if let Some(sentiment) = sentiments.next() {
+                    fields.push(("sentiment.title_negative", Value::from(negative(sentiment))));
+                }
+            }
+            if body.is_some() {
+                if let Some(sentiment) = sentiments.next() {
+                    fields.push(("sentiment.body_negative", Value::from(negative(sentiment))));
+                }
+            }
+            self.rules.apply(self.name(), result, &fields, analysis);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    // Queues one job per text list, returning the receivers for the replies
+    fn queue(jobs: &Sender<Job<String>>, texts: &[&[&str]]) -> Vec<oneshot::Receiver<Vec<String>>> {
+        texts
+            .iter()
+            .map(|t| {
+                let (reply, answer) = oneshot::channel();
+                jobs.send(Job { texts: t.iter().map(|s| s.to_string()).collect(), reply }).unwrap();
+                answer
+            })
+            .collect()
+    }
+
+    // Runs serve on its own thread with a model that upper-cases its inputs
+    // and records every batch size
+    fn worker(receiver: Receiver<Job<String>>) -> (thread::JoinHandle<()>, Arc<Mutex<Vec<usize>>>) {
+        let sizes = Arc::new(Mutex::new(vec![]));
+        let recorded = sizes.clone();
+        let handle = thread::spawn(move || {
+            serve(receiver, |inputs| {
+                recorded.lock().unwrap().push(inputs.len());
+                inputs.iter().map(|s| s.to_uppercase()).collect()
+            })
+        });
+        (handle, sizes)
+    }
+
+    #[test]
+    fn queued_jobs_are_batched_up_to_the_limit_and_answered_in_order() {
+        let (jobs, receiver) = mpsc::channel();
+        let words: Vec<String> = (0..MAX_BATCH + 7).map(|i| format!("w{}", i)).collect();
+        let mut lists: Vec<Vec<&str>> = words.iter().map(|w| vec![w.as_str()]).collect();
+        lists.push(vec!["title", "body"]);
+        let slices: Vec<&[&str]> = lists.iter().map(Vec::as_slice).collect();
+        let answers = queue(&jobs, &slices);
+        drop(jobs);
+        let (handle, sizes) = worker(receiver);
+        handle.join().unwrap();
+
+        assert_eq!(*sizes.lock().unwrap(), [MAX_BATCH, 9]);
+        let replies: Vec<Vec<String>> = answers.into_iter().map(|a| a.blocking_recv().unwrap()).collect();
+        assert_eq!(replies[0], ["W0"]);
+        assert_eq!(replies[MAX_BATCH + 6], [format!("W{}", MAX_BATCH + 6)]);
+        assert_eq!(replies.last().unwrap(), &["TITLE", "BODY"]);
+    }
+
+    #[test]
+    fn a_lone_job_is_answered_once_the_window_closes() {
+        let (jobs, receiver) = mpsc::channel();
+        let (handle, sizes) = worker(receiver);
+        let started = Instant::now();
+        let first = queue(&jobs, &[&["a"]]).remove(0).blocking_recv().unwrap();
+        assert!(started.elapsed() >= BATCH_WINDOW);
+        let second = queue(&jobs, &[&["b"]]).remove(0).blocking_recv().unwrap();
+        assert_eq!((first, second), (vec!["A".to_string()], vec!["B".to_string()]));
+        drop(jobs);
+        handle.join().unwrap();
+        assert_eq!(*sizes.lock().unwrap(), [1, 1]);
+    }
+
+    #[tokio::test]
+    async fn a_dead_worker_disables_sentiment() {
+        let rules = ScoreRules::load(None, "sentiment").unwrap();
+        // Worker gone before the request: the send fails
+        let (jobs, receiver) = mpsc::channel();
+        drop(receiver);
+        let analyzer = SentimentAnalyzer { jobs, rules };
+        assert!(analyzer.predict(vec!["text".to_string()]).await.is_none());
+        // Worker stops with the job queued (model failed to load): the reply is dropped
+        let (jobs, receiver) = mpsc::channel();
+        let analyzer = SentimentAnalyzer { jobs, rules: analyzer.rules };
+        let stopped = thread::spawn(move || drop(receiver.recv()));
+        assert!(analyzer.predict(vec!["text".to_string()]).await.is_none());
+        stopped.join().unwrap();
+    }
+}
@@ -0,0 +1,737 @@
+use crate::prober::ProbeResult;
+use crate::vuln_db::{compare_versions, Vulnerability};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Bundled default rules, compiled in so fingerprinting works out of the box
+const BUNDLED_TECHNOLOGIES: &str = include_str!("../data/technologies.json");
+const BUNDLED_CATEGORIES: &str = include_str!("../data/categories.json");
+
+// Wappalyzer allows a single pattern or a list of patterns for most fields
+pub fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(s) => vec![s],
+        OneOrMany::Many(v) => v,
+    })
+}
+
+fn map_one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Wrapper(#[serde(deserialize_with = "one_or_many")] Vec<String>);
+    let map: HashMap<String, Wrapper> = HashMap::deserialize(deserializer)?;
+    Ok(map.into_iter().map(|(k, Wrapper(v))| (k, v)).collect())
+}
+
+// One technology as written in a rule file (Wappalyzer technologies format,
+// plus the grimnir-specific "title" and "favicon" fields). Unknown fields are ignored.
+// "scripts" is matched against same-origin JS files when --fetch-js is enabled.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TechnologyDef {
+    #[serde(deserialize_with = "map_one_or_many")]
+    headers: HashMap<String, Vec<String>>,
+    cookies: HashMap<String, String>,
+    #[serde(deserialize_with = "map_one_or_many")]
+    meta: HashMap<String, Vec<String>>,
+    #[serde(deserialize_with = "one_or_many")]
+    html: Vec<String>,
+    #[serde(rename = "scriptSrc", deserialize_with = "one_or_many")]
+    script_src: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    scripts: Vec<String>,  // Content of fetched JS files: banners and global markers
+    #[serde(deserialize_with = "one_or_many")]
+    url: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    title: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    favicon: Vec<String>,  // "mmh3:<signed int>" or "md5:<hex>"
+    cats: Vec<u32>,
+    cpe: Option<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    implies: Vec<String>,   // "PHP" or "PHP\;confidence:50"
+    #[serde(deserialize_with = "one_or_many")]
+    excludes: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    requires: Vec<String>,  // Only reported when all of these are detected too
+    #[serde(rename = "requiresCategory")]
+    requires_category: Vec<u32>,  // Only reported when something in these categories is detected
+}
+
+// Wappalyzer categories.json entry ("1": {"name": "CMS", ...})
+#[derive(Deserialize)]
+struct CategoryDef {
+    name: String,
+}
+
+// A rule file is either a flat name -> technology map (Wappalyzer's
+// technologies/*.json) or the older apps.json layout with a "technologies" key
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RuleFile {
+    Wrapped { technologies: HashMap<String, TechnologyDef> },
+    Flat(HashMap<String, TechnologyDef>),
+}
+
+// A structured detection: what was found, which version, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub name: String,
+    pub version: Option<String>,
+    pub categories: Vec<String>,
+    pub confidence: u8,         // 0-100
+    pub evidence: Vec<String>,  // One line per matching pattern
+    pub cpe: Option<String>,    // CPE 2.3 with the version filled in when known
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub vulnerabilities: Vec<Vulnerability>,  // Filled by the offline CVE lookup
+}
+
+// A compiled Wappalyzer pattern with its "\;version:" and "\;confidence:" tags
+#[derive(Debug)]
+struct Pattern {
+    regex: Option<Regex>,     // None means "present with any value"
+    version: Option<String>,  // Template such as "\1" or "\1?next:legacy"
+    confidence: u8,
+}
+
+lazy_static! {
+    static ref VERSION_TERNARY_RE: Regex = Regex::new(r"^\\(\d+)\?([^:]*):(.*)$").unwrap();
+    static ref BACKREF_RE: Regex = Regex::new(r"\\(\d+)").unwrap();
+}
+
+// Expands a version template against the capture groups of a match
+fn resolve_version(template: &str, caps: &Captures) -> Option<String> {
+    let group = |idx: &str| idx.parse::<usize>().ok().and_then(|i| caps.get(i)).map_or("", |m| m.as_str());
+    let expanded = match VERSION_TERNARY_RE.captures(template) {
+        Some(t) => {
+            let branch = if group(&t[1]).is_empty() { &t[3] } else { &t[2] };
+            BACKREF_RE.replace_all(branch, |c: &Captures| group(&c[1]).to_string()).into_owned()
+        }
+        None => BACKREF_RE.replace_all(template, |c: &Captures| group(&c[1]).to_string()).into_owned(),
+    };
+    let expanded = expanded.trim().to_string();
+    if expanded.is_empty() { None } else { Some(expanded) }
+}
+
+impl Pattern {
+    // Parses "regex\;version:\1\;confidence:50"; invalid regexes are reported
+    // and skipped (the regex crate lacks lookaround and backreferences)
+    fn compile(tech: &str, raw: &str) -> Option<Pattern> {
+        let mut parts = raw.split("\\;");
+        let regex_part = parts.next().unwrap_or("");
+        let mut pattern = Pattern { regex: None, version: None, confidence: 100 };
+        for tag in parts {
+            match tag.split_once(':') {
+                Some(("version", v)) => pattern.version = Some(v.to_string()),
+                Some(("confidence", c)) => pattern.confidence = c.trim().parse().unwrap_or(100).min(100),
+                _ => {}
+            }
+        }
+        if !regex_part.is_empty() {
+            match Regex::new(&format!("(?i){}", regex_part)) {
+                Ok(re) => pattern.regex = Some(re),
+                Err(e) => {
+                    eprintln!("Skipping pattern '{}' for {}: {}", raw, tech, e);
+                    return None;
+                }
+            }
+        }
+        Some(pattern)
+    }
+
+    // On a match returns the extracted version (None if the pattern has none)
+    fn check(&self, value: &str) -> Option<Option<String>> {
+        match &self.regex {
+            None => Some(None),
+            Some(re) => {
+                let caps = re.captures(value)?;
+                Some(self.version.as_deref().and_then(|t| resolve_version(t, &caps)))
+            }
+        }
+    }
+}
+
+// A technology with its patterns compiled
+#[derive(Debug)]
+struct Technology {
+    name: String,
+    categories: Vec<String>,
+    cpe: Option<String>,
+    headers: Vec<(String, Pattern)>,
+    cookies: Vec<(String, Pattern)>,
+    meta: Vec<(String, Pattern)>,
+    html: Vec<Pattern>,
+    script_src: Vec<Pattern>,
+    scripts: Vec<Pattern>,
+    url: Vec<Pattern>,
+    title: Vec<Pattern>,
+    favicon: Vec<String>,
+    implies: Vec<(String, u8)>,  // (name, confidence)
+    excludes: Vec<String>,
+    requires: Vec<String>,
+    requires_category: Vec<String>,
+}
+
+// Splits "Name\;confidence:50" into the name and its confidence (default 100)
+fn parse_implied(raw: &str) -> (String, u8) {
+    let mut parts = raw.split("\\;");
+    let name = parts.next().unwrap_or("").trim().to_string();
+    let confidence = parts
+        .find_map(|t| t.strip_prefix("confidence:"))
+        .and_then(|c| c.trim().parse().ok())
+        .unwrap_or(100u8)
+        .min(100);
+    (name, confidence)
+}
+
+// Combines independent evidence: 1 - product of (1 - confidence)
+pub fn combine_confidence(confidences: impl Iterator<Item = u8>) -> u8 {
+    let miss: f32 = confidences.map(|c| 1.0 - c as f32 / 100.0).product();
+    ((1.0 - miss) * 100.0).round().clamp(0.0, 100.0) as u8
+}
+
+// Compiles a list of unkeyed patterns, dropping invalid ones
+fn compile_list(tech: &str, raws: &[String]) -> Vec<Pattern> {
+    raws.iter().filter_map(|r| Pattern::compile(tech, r)).collect()
+}
+
+// Compiles keyed patterns (headers, cookies, meta); keys are lowercased
+fn compile_keyed<'a>(tech: &str, entries: impl Iterator<Item = (&'a String, &'a String)>) -> Vec<(String, Pattern)> {
+    entries
+        .filter_map(|(key, raw)| Pattern::compile(tech, raw).map(|p| (key.to_lowercase(), p)))
+        .collect()
+}
+
+// Shortens matched values so evidence lines stay readable
+pub fn clip(value: &str) -> String {
+    if value.chars().count() > 80 {
+        format!("{}...", value.chars().take(80).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+// Puts the detected version into the CPE's version field (the 6th component)
+fn cpe_with_version(cpe: &str, version: Option<&str>) -> String {
+    match version {
+        Some(v) => {
+            let mut parts: Vec<&str> = cpe.split(':').collect();
+            if parts.len() > 5 {
+                parts[5] = v;
+            }
+            parts.join(":")
+        }
+        None => cpe.to_string(),
+    }
+}
+
+impl Technology {
+    fn compile(name: String, def: &TechnologyDef, categories: &HashMap<u32, String>) -> Technology {
+        Technology {
+            categories: def.cats.iter().filter_map(|id| categories.get(id).cloned()).collect(),
+            cpe: def.cpe.clone(),
+            headers: compile_keyed(&name, def.headers.iter().flat_map(|(k, v)| v.iter().map(move |p| (k, p)))),
+            cookies: compile_keyed(&name, def.cookies.iter()),
+            meta: compile_keyed(&name, def.meta.iter().flat_map(|(k, v)| v.iter().map(move |p| (k, p)))),
+            html: compile_list(&name, &def.html),
+            script_src: compile_list(&name, &def.script_src),
+            scripts: compile_list(&name, &def.scripts),
+            url: compile_list(&name, &def.url),
+            title: compile_list(&name, &def.title),
+            favicon: def.favicon.iter().map(|f| f.trim().to_lowercase()).collect(),
+            implies: def.implies.iter().map(|i| parse_implied(i)).filter(|(n, _)| !n.is_empty()).collect(),
+            excludes: def.excludes.clone(),
+            requires: def.requires.clone(),
+            requires_category: def.requires_category.iter().filter_map(|id| categories.get(id).cloned()).collect(),
+            name,
+        }
+    }
+
+    // Checks every pattern and turns the hits into a detection, if any
+    fn evaluate(&self, result: &ProbeResult, body: &str) -> Option<Detection> {
+        // (evidence, confidence, version) per matching pattern
+        let mut hits: Vec<(String, u8, Option<String>)> = vec![];
+        let mut record = |pattern: &Pattern, source: String, value: &str| {
+            if let Some(version) = pattern.check(value) {
+                hits.push((format!("{}: {}", source, clip(value)), pattern.confidence, version));
+            }
+        };
+
+        for (name, p) in &self.headers {
+            for value in result.headers.get_all(name) {
+                record(p, format!("header {}", name), value);
+            }
+        }
+        // Cookie keys match the whole name; a trailing "*" matches a prefix
+        // (e.g. "BIGipServer*" matches "BIGipServerpool_web")
+        for (name, p) in &self.cookies {
+            let matches = |cookie: &str| match name.strip_suffix('*') {
+                Some(prefix) => cookie.starts_with(prefix),
+                None => cookie == name,
+            };
+            for cookie in result.cookies.iter().filter(|c| matches(&c.name.to_lowercase())) {
+                record(p, format!("cookie {}", cookie.name), &cookie.value);
+            }
+        }
+        if let Some(html) = &result.html {
+            for (name, p) in &self.meta {
+                for meta in html.meta.iter().filter(|m| &m.name == name) {
+                    record(p, format!("meta {}", name), &meta.content);
+                }
+            }
+            for p in &self.script_src {
+                for src in &html.scripts {
+                    record(p, "script".to_string(), src);
+                }
+            }
+        }
+        if let Some(title) = &result.title {
+            for p in &self.title {
+                record(p, "title".to_string(), title);
+            }
+        }
+        for p in &self.url {
+            record(p, "url".to_string(), &result.url);
+        }
+        if !body.is_empty() {
+            for p in &self.html {
+                // Evidence shows the matched fragment, not the whole body
+                if let Some(m) = p.regex.as_ref().and_then(|re| re.find(body)) {
+                    record(p, "body".to_string(), m.as_str());
+                }
+            }
+        }
+        for asset in &result.scripts {
+            for p in &self.scripts {
+                if let Some(m) = p.regex.as_ref().and_then(|re| re.find(&asset.content)) {
+                    record(p, format!("script content {}", asset.url), m.as_str());
+                }
+            }
+        }
+        if let Some(favicon) = &result.favicon {
+            let mmh3 = format!("mmh3:{}", favicon.mmh3);
+            let md5 = format!("md5:{}", favicon.md5);
+            if let Some(f) = self.favicon.iter().find(|f| **f == mmh3 || **f == md5) {
+                hits.push((format!("favicon {}", f), 100, None));
+            }
+        }
+
+        if hits.is_empty() {
+            return None;
+        }
+
+        // Take the version from the most confident pattern, then the highest
+        // one ("2.4.41" over "2", "1.10" over "1.9.1")
+        let version = hits
+            .iter()
+            .filter_map(|(_, c, v)| v.as_ref().map(|v| (*c, v)))
+            .max_by(|(ca, a), (cb, b)| ca.cmp(cb).then_with(|| compare_versions(a, b)))
+            .map(|(_, v)| v.clone());
+        // Several patterns can match the same value (e.g. a versioned and a plain script URL)
+        let mut evidence: Vec<String> = hits.iter().map(|(e, _, _)| e.clone()).collect();
+        evidence.dedup();
+        Some(Detection {
+            name: self.name.clone(),
+            cpe: self.cpe.as_deref().map(|c| cpe_with_version(c, version.as_deref())),
+            version,
+            categories: self.categories.clone(),
+            confidence: combine_confidence(hits.iter().map(|(_, c, _)| *c)),
+            evidence,
+            vulnerabilities: vec![],
+        })
+    }
+}
+
+// The full rule set used by the fingerprinter
+#[derive(Debug, Default)]
+pub struct SignatureSet {
+    technologies: Vec<Technology>,
+    categories: HashMap<u32, String>,
+}
+
+impl SignatureSet {
+    // The default rules shipped with grimnir
+    pub fn bundled() -> SignatureSet {
+        let mut set = SignatureSet::default();
+        set.add_categories("bundled", BUNDLED_CATEGORIES, false).expect("Invalid bundled categories");
+        set.add_file_content("bundled", BUNDLED_TECHNOLOGIES, false).expect("Invalid bundled signatures");
+        set
+    }
+
+    // Loads every .json/.yaml/.yml rule file in a directory on top of this set;
+    // a technology with an existing name replaces the earlier definition.
+    // A categories.json/yaml file extends the category names.
+    pub fn load_dir(&mut self, dir: &Path) -> Result<usize, String> {
+        let entries = fs::read_dir(dir).map_err(|e| format!("Cannot read signature dir '{}': {}", dir.display(), e))?;
+        let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+
+        // Categories first, so technologies can resolve their "cats" ids
+        let is_categories = |p: &Path| p.file_stem().is_some_and(|s| s == "categories");
+        paths.sort_by_key(|p| !is_categories(p));
+
+        let mut loaded = 0;
+        for path in paths {
+            let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+            let is_yaml = ext == "yaml" || ext == "yml";
+            if !(is_yaml || ext == "json") {
+                continue;
+            }
+            let content = fs::read_to_string(&path).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+            if is_categories(&path) {
+                self.add_categories(&path.display().to_string(), &content, is_yaml)?;
+            } else {
+                loaded += self.add_file_content(&path.display().to_string(), &content, is_yaml)?;
+            }
+        }
+        Ok(loaded)
+    }
+
+    fn add_categories(&mut self, source: &str, content: &str, is_yaml: bool) -> Result<(), String> {
+        let parsed: HashMap<String, CategoryDef> = if is_yaml {
+            serde_yaml::from_str(content).map_err(|e| format!("Invalid categories file '{}': {}", source, e))?
+        } else {
+            serde_json::from_str(content).map_err(|e| format!("Invalid categories file '{}': {}", source, e))?
+        };
+        for (id, cat) in parsed {
+            if let Ok(id) = id.parse::<u32>() {
+                self.categories.insert(id, cat.name);
+            }
+        }
+        Ok(())
+    }
+
+    fn add_file_content(&mut self, source: &str, content: &str, is_yaml: bool) -> Result<usize, String> {
+        let parsed: RuleFile = if is_yaml {
+            serde_yaml::from_str(content).map_err(|e| format!("Invalid rule file '{}': {}", source, e))?
+        } else {
+            serde_json::from_str(content).map_err(|e| format!("Invalid rule file '{}': {}", source, e))?
+        };
+        let defs = match parsed {
+            RuleFile::Wrapped { technologies } => technologies,
+            RuleFile::Flat(technologies) => technologies,
+        };
+        let count = defs.len();
+        for (name, def) in defs {
+            let tech = Technology::compile(name, &def, &self.categories);
+            self.technologies.retain(|t| t.name != tech.name);
+            self.technologies.push(tech);
+        }
+        Ok(count)
+    }
+
+    fn find(&self, name: &str) -> Option<&Technology> {
+        self.technologies.iter().find(|t| t.name == name)
+    }
+
+    // Every technology with at least one matching pattern, after resolving
+    // implies/excludes/requires relationships between them
+    pub fn match_result(&self, result: &ProbeResult) -> Vec<Detection> {
+        let body = result.body_text();
+        let mut detected: Vec<Detection> = self.technologies.iter().filter_map(|t| t.evaluate(result, &body)).collect();
+        self.resolve(&mut detected);
+        detected
+    }
+
+    // A detection for a named technology found by other means (e.g. active
+    // probes), with categories and CPE taken from its signature when known
+    pub fn detection(&self, name: &str, version: Option<String>, confidence: u8, evidence: String) -> Detection {
+        let tech = self.find(name);
+        Detection {
+            name: name.to_string(),
+            cpe: tech.and_then(|t| t.cpe.as_deref()).map(|c| cpe_with_version(c, version.as_deref())),
+            version,
+            categories: tech.map(|t| t.categories.clone()).unwrap_or_default(),
+            confidence,
+            evidence: vec![evidence],
+            vulnerabilities: vec![],
+        }
+    }
+
+    // Folds extra detections into a result's list, then re-resolves relationships
+    pub fn merge(&self, detected: &mut Vec<Detection>, extra: &[Detection]) {
+        for d in extra {
+            match detected.iter_mut().find(|e| e.name == d.name) {
+                Some(existing) => {
+                    existing.confidence = combine_confidence([existing.confidence, d.confidence].into_iter());
+                    existing.evidence.extend(d.evidence.iter().cloned());
+                    if existing.version.is_none() && d.version.is_some() {
+                        existing.version = d.version.clone();
+                        existing.cpe = d.cpe.clone();
+                    }
+                }
+                None => detected.push(d.clone()),
+            }
+        }
+        self.resolve(detected);
+    }
+
+    fn resolve(&self, detected: &mut Vec<Detection>) {
+        // Implications, followed transitively (WordPress -> PHP); an implied
+        // technology that was also matched directly gains confidence instead
+        let mut i = 0;
+        while i < detected.len() {
+            let (parent, parent_conf) = (detected[i].name.clone(), detected[i].confidence);
+            let implied = self.find(&parent).map(|t| t.implies.clone()).unwrap_or_default();
+            for (name, conf) in implied {
+                let conf = (parent_conf as u32 * conf as u32 / 100) as u8;
+                let evidence = format!("implied by {}", parent);
+                match detected.iter_mut().find(|d| d.name == name) {
+                    Some(existing) => {
+                        if !existing.evidence.contains(&evidence) {
+                            existing.confidence = combine_confidence([existing.confidence, conf].into_iter());
+                            existing.evidence.push(evidence);
+                        }
+                    }
+                    None => {
+                        let tech = self.find(&name);
+                        detected.push(Detection {
+                            categories: tech.map(|t| t.categories.clone()).unwrap_or_default(),
+                            cpe: tech.and_then(|t| t.cpe.clone()),
+                            name,
+                            version: None,
+                            confidence: conf,
+                            evidence: vec![evidence],
+                            vulnerabilities: vec![],
+                        });
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        // Exclusions: a detected technology rules out the ones it names
+        let excluded: Vec<String> = detected
+            .iter()
+            .filter_map(|d| self.find(&d.name))
+            .flat_map(|t| t.excludes.iter().cloned())
+            .collect();
+        detected.retain(|d| !excluded.contains(&d.name));
+
+        // Requirements, repeated until stable since removals can cascade
+        loop {
+            let before = detected.len();
+            let names: Vec<String> = detected.iter().map(|d| d.name.clone()).collect();
+            let categories: Vec<String> = detected.iter().flat_map(|d| d.categories.iter().cloned()).collect();
+            detected.retain(|d| {
+                self.find(&d.name).is_none_or(|t| {
+                    t.requires.iter().all(|r| names.contains(r))
+                        && (t.requires_category.is_empty() || t.requires_category.iter().any(|c| categories.contains(c)))
+                })
+            });
+            if detected.len() == before {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+    use crate::js_assets::ScriptAsset;
+    use std::sync::Arc;
+
+    fn rules(json: &str) -> SignatureSet {
+        let mut set = SignatureSet::default();
+        set.add_categories("test", r#"{"1": {"name": "CMS"}, "22": {"name": "Web servers"}, "27": {"name": "Programming languages"}}"#, false)
+            .unwrap();
+        set.add_file_content("test", json, false).unwrap();
+        set
+    }
+
+    fn response(url: &str, headers: &[(&str, &str)], body: &str) -> ProbeResult {
+        test_response(url, 200, headers, body)
+    }
+
+    fn names(detections: &[Detection]) -> Vec<&str> {
+        let mut names: Vec<&str> = detections.iter().map(|d| d.name.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn bundled_rules_load() {
+        let set = SignatureSet::bundled();
+        assert!(set.find("WordPress").is_some());
+        assert!(set.find("Nginx").is_some_and(|t| !t.categories.is_empty()));
+    }
+
+    #[test]
+    fn flat_wrapped_and_yaml_files() {
+        let flat = rules(r#"{"Caddy": {"headers": {"Server": "^Caddy$"}}}"#);
+        assert_eq!(names(&flat.match_result(&response("http://t/", &[("server", "Caddy")], ""))), ["Caddy"]);
+
+        let mut set = SignatureSet::default();
+        let wrapped = r#"{"technologies": {"Shop": {"html": ["<div id=\"shop-app\"", "data-shop-theme"], "url": "/shop/"}}}"#;
+        assert_eq!(set.add_file_content("wrapped", wrapped, false).unwrap(), 1);
+        let yaml = "Tracker:\n  scriptSrc: tracker\\.example\\.com/t\\.js\n  cookies:\n    _trk: ''\n";
+        assert_eq!(set.add_file_content("yaml", yaml, true).unwrap(), 1);
+        let page = r#"<html><body data-shop-theme="dark"><script src="https://tracker.example.com/t.js"></script></body></html>"#;
+        assert_eq!(names(&set.match_result(&response("http://t/", &[("content-type", "text/html")], page))), ["Shop", "Tracker"]);
+        assert!(set.add_file_content("broken", "{not json", false).is_err());
+    }
+
+    #[test]
+    fn cookies_match_whole_names_unless_starred() {
+        let set = rules(r#"{"Sess": {"cookies": {"SID": ""}}, "Pool": {"cookies": {"BIGipServer*": ""}}}"#);
+        let cookies = |set_cookie: &str| set.match_result(&response("http://t/", &[("set-cookie", set_cookie)], ""));
+        assert_eq!(names(&cookies("sid=1")), ["Sess"]);
+        assert!(cookies("SIDEBAR=open").is_empty());
+        assert_eq!(names(&cookies("BIGipServerpool_web=1.2.3.4")), ["Pool"]);
+    }
+
+    #[test]
+    fn later_rules_replace_earlier_ones() {
+        let mut set = rules(r#"{"App": {"headers": {"X-App": "one"}}}"#);
+        set.add_file_content("override", r#"{"App": {"headers": {"X-App": "two"}}}"#, false).unwrap();
+        assert_eq!(set.technologies.len(), 1);
+        assert!(set.match_result(&response("http://t/", &[("x-app", "one")], "")).is_empty());
+        assert_eq!(set.match_result(&response("http://t/", &[("x-app", "two")], "")).len(), 1);
+    }
+
+    #[test]
+    fn invalid_patterns_are_skipped() {
+        // Lookahead is not supported by the regex crate
+        let set = rules(r#"{"App": {"html": ["foo(?=bar)", "app-root"]}}"#);
+        assert_eq!(set.find("App").unwrap().html.len(), 1);
+        assert_eq!(set.match_result(&response("http://t/", &[], "<app-root>")).len(), 1);
+    }
+
+    #[test]
+    fn version_templates() {
+        let pattern = Pattern::compile("t", r"jquery(?:-([\d.]+))?(?:\.min)?\.js\;version:\1?\1:latest\;confidence:70").unwrap();
+        assert_eq!(pattern.confidence, 70);
+        assert_eq!(pattern.check("/js/jquery-3.6.1.min.js"), Some(Some("3.6.1".to_string())));
+        assert_eq!(pattern.check("/js/jquery.js"), Some(Some("latest".to_string())));
+        assert_eq!(pattern.check("/js/app.js"), None);
+        let plain = Pattern::compile("t", r"^Apache/([\d.]+)\;version:v\1").unwrap();
+        assert_eq!(plain.check("apache/2.4.62 (Debian)"), Some(Some("v2.4.62".to_string())));
+        assert_eq!(Pattern::compile("t", "").unwrap().check("anything"), Some(None));
+    }
+
+    #[test]
+    fn detections_carry_version_categories_evidence_and_cpe() {
+        let set = rules(
+            r#"{"Apache HTTP Server": {
+                "cats": [22],
+                "cpe": "cpe:2.3:a:apache:http_server:*:*:*:*:*:*:*:*",
+                "headers": {"Server": ["Apache(?:/([\\d.]+))?\\;version:\\1", "Apache\\;confidence:50"]}
+            }}"#,
+        );
+        let detected = set.match_result(&response("http://t/", &[("server", "Apache/2.4.62 (Debian)")], ""));
+        let apache = &detected[0];
+        assert_eq!(apache.version.as_deref(), Some("2.4.62"));
+        assert_eq!(apache.categories, ["Web servers"]);
+        assert_eq!(apache.cpe.as_deref(), Some("cpe:2.3:a:apache:http_server:2.4.62:*:*:*:*:*:*:*"));
+        assert_eq!(apache.confidence, 100);
+        // Both patterns matched the same header value; it is listed once
+        assert_eq!(apache.evidence, ["header server: Apache/2.4.62 (Debian)"]);
+        let unversioned = set.match_result(&response("http://t/", &[("server", "Apache")], ""));
+        assert_eq!(unversioned[0].cpe.as_deref(), Some("cpe:2.3:a:apache:http_server:*:*:*:*:*:*:*:*"));
+    }
+
+    #[test]
+    fn version_comes_from_the_most_confident_then_highest_hit() {
+        let set = rules(
+            r#"{"Lib": {
+                "scriptSrc": ["^/lib-([\\d.]+)\\.js\\;version:\\1", "legacy/lib-([\\d.]+)\\.js\\;version:\\1\\;confidence:40"],
+                "html": "data-lib=\"([\\d.]+)\"\\;version:\\1"
+            }}"#,
+        );
+        let page = |html: &str| response("http://t/", &[("content-type", "text/html")], html);
+        // Same confidence: 1.10 is newer than 1.9.1, though shorter
+        let both = set.match_result(&page(r#"<script src="/lib-1.9.1.js"></script><div data-lib="1.10"></div>"#));
+        assert_eq!(both[0].version.as_deref(), Some("1.10"));
+        // A weak hit does not override a confident one, whatever its version
+        let weak = set.match_result(&page(r#"<script src="/legacy/lib-2.0.0.js"></script><div data-lib="1.10"></div>"#));
+        assert_eq!(weak[0].version.as_deref(), Some("1.10"));
+    }
+
+    fn related() -> SignatureSet {
+        rules(
+            r#"{
+                "WordPress": {"cats": [1], "meta": {"generator": "^WordPress"}, "implies": ["PHP", "MySQL\\;confidence:50"]},
+                "PHP": {"cats": [27], "headers": {"X-Powered-By": "^PHP"}},
+                "MySQL": {},
+                "Joomla": {"cats": [1], "html": "joomla", "excludes": "WordPress"},
+                "WP Plugin": {"html": "wp-plugin", "requires": "WordPress"},
+                "CMS Theme": {"html": "cms-theme", "requiresCategory": [1]}
+            }"#,
+        )
+    }
+
+    #[test]
+    fn confidence_combines_independent_evidence() {
+        assert_eq!(combine_confidence([50, 50].into_iter()), 75);
+        assert_eq!(combine_confidence([60, 50, 0].into_iter()), 80);
+        assert_eq!(combine_confidence([100, 10].into_iter()), 100);
+        assert_eq!(combine_confidence(std::iter::empty()), 0);
+        assert_eq!(parse_implied(r"MySQL\;confidence:50"), ("MySQL".to_string(), 50));
+        assert_eq!(parse_implied(" PHP "), ("PHP".to_string(), 100));
+    }
+
+    #[test]
+    fn implied_technologies_are_added_or_reinforced() {
+        let set = related();
+        let page = r#"<meta name="generator" content="WordPress 6.4"><div class="wp-plugin cms-theme"></div>"#;
+        let detected = set.match_result(&response("http://t/", &[("x-powered-by", "PHP/8.2")], page));
+        assert_eq!(names(&detected), ["CMS Theme", "MySQL", "PHP", "WP Plugin", "WordPress"]);
+        let find = |name: &str| detected.iter().find(|d| d.name == name).unwrap();
+        assert_eq!(find("MySQL").confidence, 50);
+        assert_eq!(find("MySQL").evidence, ["implied by WordPress"]);
+        assert_eq!(find("PHP").categories, ["Programming languages"]);
+        assert_eq!(find("PHP").evidence.last().map(String::as_str), Some("implied by WordPress"));
+    }
+
+    #[test]
+    fn exclusions_and_requirements() {
+        let set = related();
+        // Joomla rules out WordPress, which takes its plugin along
+        let page = r#"<meta name="generator" content="WordPress"><i class="joomla wp-plugin"></i>"#;
+        assert_eq!(names(&set.match_result(&response("http://t/", &[], page))), ["Joomla", "MySQL", "PHP"]);
+        // Without anything in the CMS category the theme is not reported
+        assert!(set.match_result(&response("http://t/", &[], "<i class=\"cms-theme wp-plugin\"></i>")).is_empty());
+    }
+
+    #[test]
+    fn merged_detections_fill_in_versions() {
+        let set = related();
+        let mut detected = set.match_result(&response("http://t/", &[("x-powered-by", "PHP")], ""));
+        let active = set.detection("WordPress", Some("6.4.2".to_string()), 80, "/readme.html".to_string());
+        set.merge(&mut detected, &[active]);
+        assert_eq!(names(&detected), ["MySQL", "PHP", "WordPress"]);
+        let php = detected.iter().find(|d| d.name == "PHP").unwrap();
+        assert_eq!(php.confidence, 100);
+        assert_eq!(detected.iter().find(|d| d.name == "MySQL").unwrap().confidence, 40);
+    }
+
+    #[test]
+    fn javascript_libraries_from_script_urls_and_bundles() {
+        let set = SignatureSet::bundled();
+        let page = r#"<script src="https://cdnjs.cloudflare.com/ajax/libs/jquery/3.6.0/jquery.min.js"></script>
+            <script src="/_next/static/chunks/main.js"></script>"#;
+        let mut result = response("http://t/", &[("content-type", "text/html")], page);
+        result.scripts.push(Arc::new(ScriptAsset {
+            url: "http://t/_next/static/chunks/main.js".to_string(),
+            content: "/** @license React v18.2.0 */ var a=1;".to_string(),
+        }));
+        let detected = set.match_result(&result);
+        let version = |name: &str| detected.iter().find(|d| d.name == name).map(|d| d.version.clone());
+        assert_eq!(version("jQuery"), Some(Some("3.6.0".to_string())));
+        assert_eq!(version("React"), Some(Some("18.2.0".to_string())));
+        assert!(version("Next.js").is_some() && version("Node.js").is_some());
+        let react = detected.iter().find(|d| d.name == "React").unwrap();
+        assert!(react.evidence.iter().any(|e| e.contains("main.js")), "{:?}", react.evidence);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::signatures::Detection;
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+// A vulnerability attached to a detection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Vulnerability {
+    pub id: String,
+    pub cvss: Option<f32>,
+    pub severity: Option<String>,
+    pub summary: String,
+}
+
+// One vulnerable CPE match from an NVD configuration: an exact version or a range
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CpeRange {
+    cve: String,
+    version: Option<String>,  // Exact version from the CPE itself ("*" stored as None)
+    start_including: Option<String>,
+    start_excluding: Option<String>,
+    end_including: Option<String>,
+    end_excluding: Option<String>,
+}
+
+// Local vulnerability store, keyed by "vendor:product" for scan-time lookups
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VulnDb {
+    products: HashMap<String, Vec<CpeRange>>,
+    cves: HashMap<String, Vulnerability>,
+}
+
+// Summaries are truncated so the store stays small
+const MAX_SUMMARY: usize = 300;
+
+// Default store location: $GRIMNIR_HOME or ~/.grimnir, then vulndb.json
+pub fn default_path() -> PathBuf {
+    crate::grimnir_home().join("vulndb.json")
+}
+
+// Compares dotted versions component-wise: numbers numerically, missing
+// trailing components as 0, and text below any number, so pre-releases come
+// before their release ("1.18.0" < "1.20", "2.4.9" < "2.4.41",
+// "1.0-beta" < "1.0" < "1.0.1", "1.0" == "1.0.0"). A letter suffix on a
+// number is a later patch level, OpenSSL style ("1.0.2" < "1.0.2k" < "1.0.3")
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let split = |v: &str| -> Vec<String> {
+        v.split(['.', '-', '_', '+']).filter(|p| !p.is_empty()).map(|p| p.to_lowercase()).collect()
+    };
+    // "2k" -> (Some(2), "k"), "beta" -> (None, "beta")
+    let number = |p: &str| -> (Option<u64>, String) {
+        let digits = p.len() - p.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        (p[..digits].parse().ok(), p[digits..].to_string())
+    };
+    let (pa, pb) = (split(a), split(b));
+    let zero = "0".to_string();
+    for i in 0..pa.len().max(pb.len()) {
+        let (x, y) = (number(pa.get(i).unwrap_or(&zero)), number(pb.get(i).unwrap_or(&zero)));
+        let ord = match (x.0, y.0) {
+            (Some(nx), Some(ny)) => nx.cmp(&ny).then_with(|| x.1.cmp(&y.1)),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => x.1.cmp(&y.1),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+impl CpeRange {
+    fn matches(&self, version: &str) -> bool {
+        if let Some(exact) = &self.version {
+            return compare_versions(version, exact) == Ordering::Equal;
+        }
+        // A bare "*" with no bounds means every version; too noisy to report
+        if self.start_including.is_none() && self.start_excluding.is_none() && self.end_including.is_none() && self.end_excluding.is_none() {
+            return false;
+        }
+        let cmp = |bound: &Option<String>| bound.as_deref().map(|b| compare_versions(version, b));
+        cmp(&self.start_including).is_none_or(|o| o != Ordering::Less)
+            && cmp(&self.start_excluding).is_none_or(|o| o == Ordering::Greater)
+            && cmp(&self.end_including).is_none_or(|o| o != Ordering::Greater)
+            && cmp(&self.end_excluding).is_none_or(|o| o == Ordering::Less)
+    }
+}
+
+// "cpe:2.3:a:vendor:product:version:..." -> ("vendor:product", version)
+fn split_cpe(cpe: &str) -> Option<(String, Option<String>)> {
+    let parts: Vec<&str> = cpe.split(':').collect();
+    if parts.len() < 6 || parts[0] != "cpe" {
+        return None;
+    }
+    let version = match parts[5] {
+        "*" | "-" | "" => None,
+        v => Some(v.replace('\\', "")),
+    };
+    Some((format!("{}:{}", parts[3], parts[4]), version))
+}
+
+fn str_field(v: &Value, key: &str) -> Option<String> {
+    v.get(key).and_then(|s| s.as_str()).map(|s| s.to_string())
+}
+
+fn english_description(list: Option<&Value>) -> String {
+    let text = list
+        .and_then(|l| l.as_array())
+        .and_then(|l| l.iter().find(|d| d.get("lang").and_then(|x| x.as_str()) == Some("en")))
+        .and_then(|d| str_field(d, "value"))
+        .unwrap_or_default();
+    if text.chars().count() > MAX_SUMMARY {
+        format!("{}...", text.chars().take(MAX_SUMMARY).collect::<String>())
+    } else {
+        text
+    }
+}
+
+// Walks configuration nodes (and legacy child nodes) collecting vulnerable CPE matches
+fn collect_matches(node: &Value, cve: &str, out: &mut Vec<(String, CpeRange)>) {
+    for key in ["cpeMatch", "cpe_match"] {
+        for m in node.get(key).and_then(|x| x.as_array()).into_iter().flatten() {
+            if m.get("vulnerable").and_then(|x| x.as_bool()) != Some(true) {
+                continue;
+            }
+            let criteria = str_field(m, "criteria").or_else(|| str_field(m, "cpe23Uri"));
+            if let Some((product, version)) = criteria.as_deref().and_then(split_cpe) {
+                out.push((
+                    product,
+                    CpeRange {
+                        cve: cve.to_string(),
+                        version,
+                        start_including: str_field(m, "versionStartIncluding"),
+                        start_excluding: str_field(m, "versionStartExcluding"),
+                        end_including: str_field(m, "versionEndIncluding"),
+                        end_excluding: str_field(m, "versionEndExcluding"),
+                    },
+                ));
+            }
+        }
+    }
+    for child in node.get("children").and_then(|x| x.as_array()).into_iter().flatten() {
+        collect_matches(child, cve, out);
+    }
+}
+
+// Parses one NVD feed: CVE API 2.0 / JSON 2.0 ("vulnerabilities") or legacy 1.1 ("CVE_Items")
+fn parse_feed(feed: &Value) -> Vec<(Vulnerability, Vec<(String, CpeRange)>)> {
+    let mut records = vec![];
+
+    for item in feed.get("vulnerabilities").and_then(|v| v.as_array()).into_iter().flatten() {
+        let cve = match item.get("cve") {
+            Some(c) => c,
+            None => continue,
+        };
+        let id = match str_field(cve, "id") {
+            Some(id) => id,
+            None => continue,
+        };
+        // Prefer CVSS v3.1, then v3.0, then v2
+        let metric = ["cvssMetricV31", "cvssMetricV30", "cvssMetricV2"]
+            .iter()
+            .find_map(|k| cve.get("metrics").and_then(|m| m.get(*k)).and_then(|a| a.get(0)));
+        let cvss = metric.and_then(|m| m.pointer("/cvssData/baseScore")).and_then(|s| s.as_f64()).map(|s| s as f32);
+        let severity = metric.and_then(|m| {
+            m.pointer("/cvssData/baseSeverity").or_else(|| m.get("baseSeverity")).and_then(|s| s.as_str()).map(|s| s.to_string())
+        });
+        let mut matches = vec![];
+        for config in cve.get("configurations").and_then(|c| c.as_array()).into_iter().flatten() {
+            for node in config.get("nodes").and_then(|n| n.as_array()).into_iter().flatten() {
+                collect_matches(node, &id, &mut matches);
+            }
+        }
+        let summary = english_description(cve.get("descriptions"));
+        records.push((Vulnerability { id, cvss, severity, summary }, matches));
+    }
+
+    for item in feed.get("CVE_Items").and_then(|v| v.as_array()).into_iter().flatten() {
+        let id = match item.pointer("/cve/CVE_data_meta/ID").and_then(|s| s.as_str()) {
+            Some(id) => id.to_string(),
+            None => continue,
+        };
+        let (cvss, severity) = if let Some(v3) = item.pointer("/impact/baseMetricV3/cvssV3") {
+            (v3.get("baseScore").and_then(|s| s.as_f64()), str_field(v3, "baseSeverity"))
+        } else {
+            (
+                item.pointer("/impact/baseMetricV2/cvssV2/baseScore").and_then(|s| s.as_f64()),
+                item.pointer("/impact/baseMetricV2/severity").and_then(|s| s.as_str()).map(|s| s.to_string()),
+            )
+        };
+        let mut matches = vec![];
+        for node in item.pointer("/configurations/nodes").and_then(|n| n.as_array()).into_iter().flatten() {
+            collect_matches(node, &id, &mut matches);
+        }
+        let summary = english_description(item.pointer("/cve/description/description_data"));
+        records.push((Vulnerability { id, cvss: cvss.map(|s| s as f32), severity, summary }, matches));
+    }
+
+    records
+}
+
+// Reads a feed file, transparently decompressing .gz feeds
+fn read_feed(path: &Path) -> Result<Value, String> {
+    let file = File::open(path).map_err(|e| format!("Cannot open '{}': {}", path.display(), e))?;
+    let mut reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+        Box::new(GzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    };
+    let mut content = String::new();
+    reader.read_to_string(&mut content).map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid NVD feed '{}': {}", path.display(), e))
+}
+
+impl VulnDb {
+    pub fn load(path: &Path) -> Result<VulnDb, String> {
+        let file = File::open(path).map_err(|e| format!("Cannot open vulnerability database '{}': {}", path.display(), e))?;
+        serde_json::from_reader(BufReader::new(file)).map_err(|e| format!("Invalid vulnerability database '{}': {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| format!("Cannot create '{}': {}", parent.display(), e))?;
+        }
+        let file = File::create(path).map_err(|e| format!("Cannot write '{}': {}", path.display(), e))?;
+        serde_json::to_writer(BufWriter::new(file), self).map_err(|e| format!("Cannot write '{}': {}", path.display(), e))
+    }
+
+    pub fn cve_count(&self) -> usize {
+        self.cves.len()
+    }
+
+    // Adds (or replaces) every CVE in a feed; returns how many were imported
+    pub fn import_feed(&mut self, path: &Path) -> Result<usize, String> {
+        let records = parse_feed(&read_feed(path)?);
+        let count = records.len();
+        self.merge(records);
+        Ok(count)
+    }
+
+    // A re-imported CVE replaces everything stored for it, so ranges or CPE
+    // matches dropped from the feed are dropped here too
+    fn merge(&mut self, records: Vec<(Vulnerability, Vec<(String, CpeRange)>)>) {
+        let ids: HashSet<&str> = records.iter().map(|(v, _)| v.id.as_str()).collect();
+        for ranges in self.products.values_mut() {
+            ranges.retain(|r| !ids.contains(r.cve.as_str()));
+        }
+        self.products.retain(|_, ranges| !ranges.is_empty());
+        self.cves.retain(|id, _| !ids.contains(id.as_str()));
+        for (vuln, matches) in records {
+            // CVEs without CPE data can never match a detection; skip them
+            if matches.is_empty() {
+                continue;
+            }
+            for (product, range) in matches {
+                self.products.entry(product).or_default().push(range);
+            }
+            self.cves.insert(vuln.id.clone(), vuln);
+        }
+    }
+
+    // Vulnerabilities affecting a detection, highest CVSS first.
+    // Needs both a CPE and a version; NVD platform ("running on") conditions are not evaluated.
+    pub fn lookup(&self, detection: &Detection) -> Vec<Vulnerability> {
+        let (cpe, version) = match (&detection.cpe, &detection.version) {
+            (Some(c), Some(v)) => (c, v),
+            _ => return vec![],
+        };
+        let product = match split_cpe(cpe) {
+            Some((p, _)) => p,
+            None => return vec![],
+        };
+        let mut seen = HashSet::new();
+        let mut found: Vec<Vulnerability> = self
+            .products
+            .get(&product)
+            .into_iter()
+            .flatten()
+            .filter(|r| r.matches(version) && seen.insert(r.cve.clone()))
+            .filter_map(|r| self.cves.get(&r.cve).cloned())
+            .collect();
+        found.sort_by(|a, b| b.cvss.unwrap_or(0.0).total_cmp(&a.cvss.unwrap_or(0.0)).then_with(|| a.id.cmp(&b.id)));
+        found
+    }
+
+    // Attaches matching vulnerabilities to each detection in place
+    pub fn annotate(&self, detections: &mut [Detection]) {
+        for detection in detections.iter_mut() {
+            detection.vulnerabilities = self.lookup(detection);
+        }
+    }
+}
+
+// `grimnir db import`: merges NVD feeds into the local store
+pub fn import(files: &[String], db_path: &Path) -> Result<(), String> {
+    let mut db = if db_path.exists() { VulnDb::load(db_path)? } else { VulnDb::default() };
+    for file in files {
+        let count = db.import_feed(Path::new(file))?;
+        println!("Imported {} CVE(s) from '{}'", count, file);
+    }
+    db.save(db_path)?;
+    println!("Vulnerability database '{}' now holds {} CVE(s) with CPE data.", db_path.display(), db.cve_count());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_components_compare_as_numbers() {
+        assert_eq!(compare_versions("1.18.0", "1.20"), Ordering::Less);
+        assert_eq!(compare_versions("2.4.9", "2.4.41"), Ordering::Less);
+        assert_eq!(compare_versions("10.0", "9.9.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn missing_components_count_as_zero() {
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0.0", "1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn pre_releases_come_before_releases() {
+        assert_eq!(compare_versions("1.0-beta", "1.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0-beta", "1.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0", "1.0-rc1"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0-alpha", "1.0-beta"), Ordering::Less);
+    }
+
+    #[test]
+    fn letter_suffixes_come_after_the_number() {
+        assert_eq!(compare_versions("1.0.2k", "1.0.2"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.2k", "1.0.2l"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.2k", "1.0.3"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.2k", "1.0.2k"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0.2k", "1.0-beta"), Ordering::Greater);
+    }
+
+    #[test]
+    fn ranges_respect_pre_releases_and_exact_versions() {
+        let range = CpeRange {
+            cve: "CVE-0000-0001".to_string(),
+            version: None,
+            start_including: None,
+            start_excluding: None,
+            end_including: None,
+            end_excluding: Some("1.0".to_string()),
+        };
+        assert!(range.matches("1.0-beta"));
+        assert!(!range.matches("1.0"));
+        assert!(!range.matches("1.0.0"));
+        let exact = CpeRange { version: Some("1.0".to_string()), end_excluding: None, ..range };
+        assert!(exact.matches("1.0.0"));
+    }
+
+    fn feed(cpe_matches: Value) -> Value {
+        serde_json::json!({"vulnerabilities": [{"cve": {
+            "id": "CVE-2021-23017",
+            "descriptions": [{"lang": "en", "value": "Off-by-one in the nginx resolver"}],
+            "metrics": {"cvssMetricV31": [{"cvssData": {"baseScore": 7.7, "baseSeverity": "HIGH"}}]},
+            "configurations": [{"nodes": [{"cpeMatch": cpe_matches}]}]
+        }}]})
+    }
+
+    #[test]
+    fn feeds_are_parsed_into_ranges() {
+        let records = parse_feed(&feed(serde_json::json!([
+            {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*", "versionStartIncluding": "0.6.18", "versionEndExcluding": "1.20.1"},
+            {"vulnerable": false, "criteria": "cpe:2.3:o:debian:debian_linux:10.0:*:*:*:*:*:*:*"}
+        ])));
+        assert_eq!(records.len(), 1);
+        let (vuln, matches) = &records[0];
+        assert_eq!(vuln.cvss, Some(7.7));
+        assert_eq!(vuln.severity.as_deref(), Some("HIGH"));
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, "f5:nginx");
+        assert!(matches[0].1.matches("1.18.0") && !matches[0].1.matches("1.20.1"));
+    }
+
+    #[test]
+    fn reimport_replaces_stale_entries() {
+        let mut db = VulnDb::default();
+        db.merge(parse_feed(&feed(serde_json::json!([
+            {"vulnerable": true, "criteria": "cpe:2.3:a:f5:nginx:*:*:*:*:*:*:*:*", "versionEndExcluding": "1.20.1"}
+        ]))));
+        assert_eq!(db.cve_count(), 1);
+        // The updated record moves the match to another product
+        db.merge(parse_feed(&feed(serde_json::json!([
+            {"vulnerable": true, "criteria": "cpe:2.3:a:nginx:nginx:*:*:*:*:*:*:*:*", "versionEndExcluding": "1.20.1"}
+        ]))));
+        assert!(!db.products.contains_key("f5:nginx"));
+        assert_eq!(db.products["nginx:nginx"].len(), 1);
+        // ... and then loses its CPE data altogether
+        db.merge(parse_feed(&feed(serde_json::json!([]))));
+        assert_eq!(db.cve_count(), 0);
+        assert!(db.products.is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use crate::prober::{probe_url, ProbeResult};
+use crate::signatures::{clip, combine_confidence};
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Bundled WAF/CDN signatures
+const BUNDLED_WAFS: &str = include_str!("../data/wafs.json");
+
+// Query parameter carrying the malicious-looking probe payload
+const PROBE_PARAM: &str = "grimnir";
+
+// Classic XSS, SQLi and traversal strings every WAF rule set reacts to
+const PROBE_PAYLOAD: &str = "<script>alert(1)</script>' OR '1'='1' -- ../../../../etc/passwd";
+
+// Statuses WAFs typically answer blocked requests with
+pub const BLOCK_STATUSES: [u16; 6] = [403, 406, 419, 429, 501, 503];
+
+// Confidence of each kind of evidence (combined like tech detections)
+const HEADER_CONFIDENCE: u8 = 60;
+const COOKIE_CONFIDENCE: u8 = 50;
+const BLOCK_PAGE_CONFIDENCE: u8 = 80;
+const PAGE_TEXT_CONFIDENCE: u8 = 30;  // Block-page text on a normal response
+const GENERIC_BLOCK_CONFIDENCE: u8 = 60;
+
+// One vendor as written in data/wafs.json
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WafDef {
+    kind: String,
+    headers: HashMap<String, String>,  // Header -> regex ("" means present)
+    cookies: Vec<String>,              // Cookie name prefixes
+    body: Vec<String>,                 // Block-page patterns
+    hints: Vec<String>,
+}
+
+// A vendor with its patterns compiled
+#[derive(Debug)]
+struct Waf {
+    vendor: String,
+    kind: String,
+    headers: Vec<(String, Option<Regex>)>,
+    cookies: Vec<String>,
+    body: Vec<Regex>,
+    hints: Vec<String>,
+}
+
+// A detected WAF/CDN, with the evidence behind it and vendor-specific hints
+#[derive(Debug, Clone, Serialize)]
+pub struct WafDetection {
+    pub vendor: String,
+    pub kind: String,
+    pub confidence: u8,  // 0-100
+    pub evidence: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hints: Vec<String>,
+}
+
+fn compile(vendor: &str, raw: &str) -> Option<Regex> {
+    match Regex::new(&format!("(?i){}", raw)) {
+        Ok(re) => Some(re),
+        Err(e) => {
+            eprintln!("Skipping WAF pattern '{}' for {}: {}", raw, vendor, e);
+            None
+        }
+    }
+}
+
+impl Waf {
+    fn compile(vendor: String, def: WafDef) -> Waf {
+        Waf {
+            headers: def
+                .headers
+                .iter()
+                .filter_map(|(name, raw)| {
+                    let re = if raw.is_empty() { None } else { Some(compile(&vendor, raw)?) };
+                    Some((name.to_lowercase(), re))
+                })
+                .collect(),
+            cookies: def.cookies.iter().map(|c| c.to_lowercase()).collect(),
+            body: def.body.iter().filter_map(|raw| compile(&vendor, raw)).collect(),
+            kind: def.kind,
+            hints: def.hints,
+            vendor,
+        }
+    }
+
+    fn evaluate(&self, result: &ProbeResult, body: &str) -> Option<WafDetection> {
+        let mut hits: Vec<(String, u8)> = vec![];
+        for (name, re) in &self.headers {
+            let matched = result.headers.get_all(name).find(|v| re.as_ref().is_none_or(|re| re.is_match(v)));
+            if let Some(value) = matched {
+                hits.push((format!("header {}: {}", name, clip(value)), HEADER_CONFIDENCE));
+            }
+        }
+        for prefix in &self.cookies {
+            if let Some(cookie) = result.cookies.iter().find(|c| c.name.to_lowercase().starts_with(prefix.as_str())) {
+                hits.push((format!("cookie {}", cookie.name), COOKIE_CONFIDENCE));
+            }
+        }
+        // Block-page text counts fully only on an error response
+        let is_block = BLOCK_STATUSES.contains(&result.status);
+        for re in &self.body {
+            if let Some(m) = re.find(body) {
+                let confidence = if is_block { BLOCK_PAGE_CONFIDENCE } else { PAGE_TEXT_CONFIDENCE };
+                hits.push((format!("body ({}): {}", result.status, clip(m.as_str())), confidence));
+            }
+        }
+        if hits.is_empty() {
+            return None;
+        }
+        Some(WafDetection {
+            vendor: self.vendor.clone(),
+            kind: self.kind.clone(),
+            confidence: combine_confidence(hits.iter().map(|(_, c)| *c)),
+            evidence: hits.into_iter().map(|(e, _)| e).collect(),
+            hints: self.hints.clone(),
+        })
+    }
+}
+
+// All known WAF/CDN vendors
+#[derive(Debug, Default)]
+pub struct WafSet {
+    wafs: Vec<Waf>,
+}
+
+impl WafSet {
+    pub fn bundled() -> WafSet {
+        let defs: HashMap<String, WafDef> = serde_json::from_str(BUNDLED_WAFS).expect("Invalid bundled WAF signatures");
+        let mut wafs: Vec<Waf> = defs.into_iter().map(|(vendor, def)| Waf::compile(vendor, def)).collect();
+        wafs.sort_by(|a, b| a.vendor.cmp(&b.vendor));
+        WafSet { wafs }
+    }
+
+    // Passive check of one response: the most likely vendor, if any
+    pub fn detect(&self, result: &ProbeResult) -> Option<WafDetection> {
+        let body = result.body_text();
+        self.wafs
+            .iter()
+            .filter_map(|w| w.evaluate(result, &body))
+            .max_by_key(|d| d.confidence)
+    }
+
+    // Active check: compares a normal request with a malicious-looking one.
+    // A vendor is named when its fingerprints show up in either response;
+    // a block without fingerprints is reported as a generic WAF. Err when the
+    // normal request fails, as nothing can be said about the target then.
+    pub async fn probe(
+        &self,
+        client: &Client,
+        url: &str,
+        headers: &[(String, String)],
+        max_body: usize,
+    ) -> Result<Option<WafDetection>, String> {
+        let mut probe_url_parsed = Url::parse(url).map_err(|e| format!("invalid URL '{}': {}", url, e))?;
+        probe_url_parsed.query_pairs_mut().append_pair(PROBE_PARAM, PROBE_PAYLOAD);
+
+        let baseline = probe_url(url.to_string(), client, "GET", None, headers.to_vec(), max_body)
+            .await
+            .map_err(|e| format!("baseline request failed: {}", e))?;
+        let attacked = probe_url(probe_url_parsed.to_string(), client, "GET", None, headers.to_vec(), max_body).await;
+
+        let mut detection = self.detect(&baseline);
+        let (blocked, block_evidence) = match &attacked {
+            Ok(res) => {
+                if let Some(d) = self.detect(res) {
+                    if detection.as_ref().is_none_or(|b| d.confidence > b.confidence) {
+                        detection = Some(d);
+                    }
+                }
+                let blocked = res.status != baseline.status && BLOCK_STATUSES.contains(&res.status);
+                (blocked, format!("malicious probe answered {} (baseline {})", res.status, baseline.status))
+            }
+            // Some WAFs drop or reset the connection instead of answering
+            Err(e) => (true, format!("malicious probe failed: {}", e)),
+        };
+
+        if !blocked {
+            return Ok(detection);
+        }
+        Ok(Some(match detection {
+            Some(mut d) => {
+                d.confidence = combine_confidence([d.confidence, GENERIC_BLOCK_CONFIDENCE].into_iter());
+                d.evidence.push(block_evidence);
+                d
+            }
+            None => WafDetection {
+                vendor: "Generic".to_string(),
+                kind: "WAF".to_string(),
+                confidence: GENERIC_BLOCK_CONFIDENCE,
+                evidence: vec![block_evidence],
+                hints: vec!["Unknown filter blocked the payload; vary encodings and payload placement to map its rules".to_string()],
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+
+    #[test]
+    fn bundled_signatures_compile() {
+        let wafs = WafSet::bundled();
+        assert!(wafs.wafs.iter().any(|w| w.vendor == "Cloudflare"));
+        assert!(wafs.wafs.iter().all(|w| !w.headers.is_empty() || !w.cookies.is_empty() || !w.body.is_empty()));
+    }
+
+    #[test]
+    fn vendor_headers_and_cookies_add_up() {
+        let result = test_response(
+            "http://t/",
+            200,
+            &[("server", "cloudflare"), ("cf-ray", "8a1b2c3d4e5f-AMS"), ("set-cookie", "__cf_bm=abc; path=/; HttpOnly")],
+            "<html>ok</html>",
+        );
+        let detection = WafSet::bundled().detect(&result).unwrap();
+        assert_eq!(detection.vendor, "Cloudflare");
+        assert_eq!(detection.confidence, 92);
+        assert!(detection.evidence.contains(&"cookie __cf_bm".to_string()));
+        assert!(!detection.hints.is_empty());
+    }
+
+    #[test]
+    fn aws_cdn_and_load_balancer_traces_are_not_a_waf() {
+        let cdn = test_response(
+            "http://t/",
+            200,
+            &[("server", "CloudFront"), ("x-amz-cf-id", "abc=="), ("x-amz-cf-pop", "AMS50-C1"), ("set-cookie", "AWSALB=xyz; Path=/")],
+            "<html>ok</html>",
+        );
+        assert!(WafSet::bundled().detect(&cdn).is_none());
+        let token = WafSet::bundled().detect(&test_response("http://t/", 200, &[("set-cookie", "aws-waf-token=t; Path=/")], "")).unwrap();
+        assert_eq!(token.vendor, "AWS WAF");
+        let page = "<H1>403 ERROR</H1><H2>The request could not be satisfied.</H2>Request blocked. We can't connect to the server for this app or website at this time.<BR>Generated by cloudfront (CloudFront)";
+        let blocked = WafSet::bundled().detect(&test_response("http://t/", 403, &[("server", "CloudFront")], page)).unwrap();
+        assert_eq!((blocked.vendor.as_str(), blocked.confidence), ("AWS WAF", BLOCK_PAGE_CONFIDENCE));
+    }
+
+    #[test]
+    fn block_page_text_counts_more_on_block_statuses() {
+        let page = "<title>Attention Required! | Cloudflare</title>";
+        let blocked = WafSet::bundled().detect(&test_response("http://t/", 403, &[], page)).unwrap();
+        assert_eq!((blocked.vendor.as_str(), blocked.confidence), ("Cloudflare", BLOCK_PAGE_CONFIDENCE));
+        let mentioned = WafSet::bundled().detect(&test_response("http://t/", 200, &[], page)).unwrap();
+        assert_eq!(mentioned.confidence, PAGE_TEXT_CONFIDENCE);
+    }
+
+    #[test]
+    fn plain_responses_have_no_waf() {
+        let result = test_response("http://t/", 404, &[("server", "nginx/1.24.0"), ("content-type", "text/html")], "<h1>Not Found</h1>");
+        assert!(WafSet::bundled().detect(&result).is_none());
+    }
+
+    #[tokio::test]
+    async fn unreachable_target_is_an_error_not_no_waf() {
+        let client = Client::builder().no_proxy().build().unwrap();
+        let outcome = WafSet::bundled().probe(&client, "http://127.0.0.1:9/", &[], 1024).await;
+        assert!(outcome.unwrap_err().starts_with("baseline request failed"));
+        assert!(WafSet::bundled().probe(&client, "not a url", &[], 1024).await.is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::prober::ProbeResult;
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, Write};
+
+// How much one sighting of a word counts, by where it was seen: paths are
+// the best predictors of other paths, prose the worst
+const PATH_WEIGHT: f32 = 3.0;
+const KEY_WEIGHT: f32 = 2.0;       // JSON keys, form fields, id/name attributes
+const IDENTIFIER_WEIGHT: f32 = 1.0;  // JS function/variable names
+const TEXT_WEIGHT: f32 = 0.5;      // Title and comment words
+
+// Words longer than this are tokens, hashes or minified noise
+const MAX_WORD_LEN: usize = 40;
+
+// Common words that are never useful paths
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "this", "that", "from", "your", "you", "are", "was", "not", "but", "all", "can",
+    "has", "have", "will", "our", "its", "http", "https", "www", "com", "html", "htm", "body", "head", "div", "span",
+    "class", "style", "script", "function", "return", "var", "let", "const", "true", "false", "null", "undefined",
+    "window", "document", "new", "typeof", "else", "void", "prototype", "length", "object", "string",
+];
+
+lazy_static! {
+    // Quoted path-like strings in HTML and JS: "/api/v1/users", 'static/app.js'
+    static ref QUOTED_PATH_RE: Regex =
+        Regex::new(r#"["'`]((?:\.{0,2}/)?[A-Za-z0-9_\-]+(?:/[A-Za-z0-9_\-.]+)+/?|/[A-Za-z0-9_\-.]+)["'`]"#).unwrap();
+    static ref ATTR_RE: Regex = Regex::new(r#"(?i)\b(?:id|name)\s*=\s*["']([A-Za-z_][\w-]{2,40})["']"#).unwrap();
+    static ref IDENTIFIER_RE: Regex = Regex::new(r"\b(?:function|const|let|var)\s+([A-Za-z_$][\w$]{2,40})").unwrap();
+    static ref WORD_RE: Regex = Regex::new(r"[A-Za-z][A-Za-z0-9_-]{2,39}").unwrap();
+}
+
+// Collects target-specific words from responses and ranks them, so a scan
+// can fuzz with what the target itself uses
+pub struct WordHarvester {
+    scores: HashMap<String, f32>,
+    tried: HashSet<String>,  // Already queued (the input wordlist and earlier rounds)
+}
+
+impl WordHarvester {
+    pub fn new(tried: &[String]) -> WordHarvester {
+        WordHarvester { scores: HashMap::new(), tried: tried.iter().map(|w| w.trim().to_string()).collect() }
+    }
+
+    pub fn harvest(&mut self, result: &ProbeResult) {
+        let page = Url::parse(&result.url).ok();
+        let body = if result.is_binary() { String::new() } else { result.body_text().into_owned() };
+
+        // Same-origin links, script sources and form actions
+        if let (Some(page), Some(html)) = (&page, &result.html) {
+            let targets = html.links.iter().chain(&html.scripts).chain(html.forms.iter().filter_map(|f| f.action.as_ref()));
+            for target in targets {
+                if let Ok(url) = page.join(target) {
+                    if url.origin() == page.origin() {
+                        self.add_path(url.path(), PATH_WEIGHT);
+                    }
+                }
+            }
+            for input in html.forms.iter().flat_map(|f| &f.inputs) {
+                if let Some(name) = &input.name {
+                    self.add(name, KEY_WEIGHT);
+                }
+            }
+            let title_words: Vec<String> = html.title.iter().chain(&html.comments).flat_map(|t| words(t)).collect();
+            for word in title_words {
+                self.add(&word.to_lowercase(), TEXT_WEIGHT);
+            }
+        }
+
+        // Paths and identifiers in inline and fetched JavaScript. Of an HTML
+        // page only the inline scripts are scanned: its links, forms and
+        // inputs were counted from the parsed document above.
+        let own: Vec<&str> = match &result.html {
+            Some(html) => html.inline_scripts.iter().map(String::as_str).collect(),
+            None => vec![body.as_str()],
+        };
+        for text in own.into_iter().chain(result.scripts.iter().map(|s| s.content.as_str())) {
+            for caps in QUOTED_PATH_RE.captures_iter(text) {
+                self.add_path(&caps[1], PATH_WEIGHT);
+            }
+            for caps in ATTR_RE.captures_iter(text) {
+                self.add(&caps[1], KEY_WEIGHT);
+            }
+            for caps in IDENTIFIER_RE.captures_iter(text) {
+                self.add(&caps[1], IDENTIFIER_WEIGHT);
+            }
+        }
+
+        // API responses: every key of a JSON document
+        let trimmed = body.trim_start();
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            if let Ok(value) = serde_json::from_str::<serde_json::Value>(trimmed) {
+                let mut keys = vec![];
+                json_keys(&value, &mut keys);
+                for key in keys {
+                    self.add(&key, KEY_WEIGHT);
+                }
+            }
+        }
+    }
+
+    fn add_path(&mut self, path: &str, weight: f32) {
+        for segment in path.split('/').filter(|s| !s.is_empty() && *s != "." && *s != "..") {
+            self.add(segment, weight);
+        }
+    }
+
+    fn add(&mut self, word: &str, weight: f32) {
+        let word = word.trim();
+        let usable = word.len() >= 3
+            && word.len() <= MAX_WORD_LEN
+            && word.chars().all(|c| c.is_ascii_alphanumeric() || "_-.~".contains(c))
+            && word.chars().any(|c| c.is_ascii_alphabetic())
+            && !(word.len() >= 16 && word.chars().all(|c| c.is_ascii_hexdigit()))
+            && !word.starts_with('.')
+            && !word.ends_with('.')
+            && !STOPWORDS.contains(&word.to_lowercase().as_str());
+        if usable {
+            *self.scores.entry(word.to_string()).or_default() += weight;
+        }
+    }
+
+    // Every harvested word, best first (ties alphabetical, for stable output)
+    pub fn ranked(&self) -> Vec<(&str, f32)> {
+        let mut ranked: Vec<(&str, f32)> = self.scores.iter().map(|(w, s)| (w.as_str(), *s)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+        ranked
+    }
+
+    // The best words not fuzzed yet; they count as tried from now on
+    pub fn next_words(&mut self, max: usize) -> Vec<String> {
+        let words: Vec<String> = self
+            .ranked()
+            .into_iter()
+            .filter(|(w, _)| !self.tried.contains(*w))
+            .take(max)
+            .map(|(w, _)| w.to_string())
+            .collect();
+        self.tried.extend(words.iter().cloned());
+        words
+    }
+
+    // Writes the ranked words, one per line, ready to use with -w
+    pub fn save(&self, path: &str) -> io::Result<usize> {
+        let ranked = self.ranked();
+        let mut file = File::create(path)?;
+        for (word, _) in &ranked {
+            writeln!(file, "{}", word)?;
+        }
+        Ok(ranked.len())
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    WORD_RE.find_iter(text).map(|m| m.as_str().to_string()).collect()
+}
+
+fn json_keys(value: &serde_json::Value, keys: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, child) in map {
+                keys.push(key.clone());
+                json_keys(child, keys);
+            }
+        }
+        serde_json::Value::Array(items) => items.iter().for_each(|item| json_keys(item, keys)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prober::test_response;
+
+    fn response(url: &str, content_type: &str, body: &str) -> ProbeResult {
+        test_response(url, 200, &[("content-type", content_type)], body)
+    }
+
+    fn score(harvester: &WordHarvester, word: &str) -> Option<f32> {
+        harvester.scores.get(word).copied()
+    }
+
+    #[test]
+    fn unusable_words_are_skipped() {
+        let mut harvester = WordHarvester::new(&[]);
+        for word in ["ab", "the", "12345", "d41d8cd98f00b204e9800998ecf8427e", ".env", "backup.", "a/b", "config.php", " admin "] {
+            harvester.add(word, 1.0);
+        }
+        let mut kept: Vec<&str> = harvester.ranked().into_iter().map(|(w, _)| w).collect();
+        kept.sort();
+        assert_eq!(kept, ["admin", "config.php"]);
+    }
+
+    #[test]
+    fn words_are_weighted_by_where_they_appear() {
+        let mut harvester = WordHarvester::new(&[]);
+        let page = r#"<html><head><title>Billing portal</title><script src="/static/billing.js"></script></head>
+            <body><a href="/invoices/archive">Old</a><a href="https://other.example/external">x</a>
+            <form action="/billing/export"><input name="invoiceId"></form>
+            <script>function exportCsv() { fetch("/api/reports"); }</script></body></html>"#;
+        harvester.harvest(&response("http://t/", "text/html", page));
+        // Links, scripts, forms and inputs count once each, from the parsed document
+        assert_eq!(score(&harvester, "invoices"), Some(PATH_WEIGHT));
+        assert_eq!(score(&harvester, "reports"), Some(PATH_WEIGHT));
+        assert_eq!(score(&harvester, "billing.js"), Some(PATH_WEIGHT));
+        assert_eq!(score(&harvester, "external"), None);
+        assert_eq!(score(&harvester, "invoiceId"), Some(KEY_WEIGHT));
+        assert_eq!(score(&harvester, "exportCsv"), Some(IDENTIFIER_WEIGHT));
+        assert_eq!(score(&harvester, "portal"), Some(TEXT_WEIGHT));
+        assert_eq!(harvester.ranked()[0], ("billing", TEXT_WEIGHT + PATH_WEIGHT));
+    }
+
+    #[test]
+    fn json_keys_are_harvested() {
+        let mut harvester = WordHarvester::new(&[]);
+        harvester.harvest(&response("http://t/api", "application/json", r#"{"users": [{"userName": "x", "roles": []}]}"#));
+        for key in ["users", "userName", "roles"] {
+            assert_eq!(score(&harvester, key), Some(KEY_WEIGHT), "{}", key);
+        }
+    }
+
+    #[test]
+    fn next_words_skips_tried_words() {
+        let mut harvester = WordHarvester::new(&["admin".to_string()]);
+        harvester.add("admin", 5.0);
+        harvester.add("backup", 2.0);
+        harvester.add("uploads", 3.0);
+        harvester.add("staging", 1.0);
+        assert_eq!(harvester.next_words(2), ["uploads", "backup"]);
+        assert_eq!(harvester.next_words(5), ["staging"]);
+        assert!(harvester.next_words(5).is_empty());
+    }
+}